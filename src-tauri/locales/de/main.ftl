@@ -202,6 +202,12 @@ unlock = Entsperren
     .create-account = Konto erstellen
     .unlock-account = Konto entsperren
 
+passphrase = Passphrase
+    .too-short = Diese Passphrase ist zu kurz.
+    .add-variety = Kombiniere Groß- und Kleinbuchstaben, Zahlen und Symbole.
+    .avoid-repeats = Vermeide wiederholte Zeichen und Folgen wie "abc" oder "123".
+    .too-weak = Diese Passphrase ist zu leicht zu erraten.
+
 auth = Konto erstellen
     .enter-username = Benutzername eingeben
 
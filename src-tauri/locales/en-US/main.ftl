@@ -38,6 +38,7 @@ messages = Messages
     .you = You
     .say-something-placeholder = Say Something...
     .user-sent-message = sent you a message.
+    .user-mentioned-you = mentioned you.
     .unknown-sent-message = someone sent you a message.
 
 favorites = Favorites
@@ -68,6 +69,7 @@ friends = Friends
     .deny = Deny Request
     .request-sent = Friend Request Sent!
     .new_request = New friend request.
+    .user-sent-request = sent you a friend request.
     .copied-did = Copied ID to clipboard!
     .unblock = Unblock
 
@@ -207,6 +209,12 @@ unlock = Unlock
     .create-account = Create Account
     .unlock-account = Unlock Account
 
+passphrase = Passphrase
+    .too-short = This passphrase is too short.
+    .add-variety = Mix upper and lower case letters, numbers, and symbols.
+    .avoid-repeats = Avoid repeated characters and sequences like "abc" or "123".
+    .too-weak = This passphrase is too easy to guess.
+
 auth = Create Account 
     .enter-username = Enter Username
 
@@ -78,6 +78,16 @@ pub struct StaticArgs {
     pub login_config_path: PathBuf,
     /// todo: document
     pub extensions_path: PathBuf,
+    /// link previews fetched for messages, one json file per url
+    pub link_preview_cache_path: PathBuf,
+    /// imported conversation archives, one folder per conversation
+    pub archives_path: PathBuf,
+    /// thumbnails of files in Constellation, named by the hash of the file they were made from
+    pub thumbnail_cache_path: PathBuf,
+    /// the sha256 of every uploaded file and where it was put in Constellation, to find duplicate uploads
+    pub content_index_path: PathBuf,
+    /// where each item in the trash was deleted from, and when
+    pub trash_index_path: PathBuf,
     /// seconds
     pub typing_indicator_refresh: u64,
     /// seconds
@@ -100,6 +110,11 @@ pub static STATIC_ARGS: Lazy<StaticArgs> = Lazy::new(|| {
         themes_path: uplink_container.join("themes"),
         cache_path: uplink_path.join("state.json"),
         extensions_path: uplink_container.join("extensions"),
+        link_preview_cache_path: uplink_path.join("link_previews"),
+        archives_path: uplink_path.join("archives"),
+        thumbnail_cache_path: uplink_path.join("thumbnails"),
+        content_index_path: uplink_path.join("content_index.json"),
+        trash_index_path: uplink_path.join("trash.json"),
         mock_cache_path: uplink_path.join("mock-state.json"),
         warp_path: warp_path.clone(),
         logger_path: uplink_path.join("debug.log"),
@@ -16,35 +16,66 @@ use std::sync::mpsc::{Receiver, Sender};
 use tokio::runtime::Handle;
 pub mod config;
 // pub mod utils;
+// these are in lib.rs too. state uses them for desktop notifications, the rest is only used by the library
+#[allow(dead_code)]
+mod language;
+#[allow(dead_code)]
+mod notifications;
+#[allow(dead_code)]
+mod sounds;
 use futures::channel::oneshot;
 use std::str::FromStr;
 use std::sync::Mutex;
 use std::sync::MutexGuard;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tauri::Manager;
 use tokio::time::sleep;
 use warp::error::Error;
 mod warp_runner;
 use crate::warp_runner::{
+    account_deletion::DeletionReport,
+    archive::{ExportFormat, ExportRange},
+    content_index::{DuplicateAction, DuplicateUpload},
+    passphrase::{self, PassphraseStrength},
+    safety_number::SafetyNumber,
+    thumbnails::ThumbnailSize,
+    trash::TrashEntry,
     ui_adapter::{MessageEvent, MultiPassEvent, RayGunEvent},
-    ConstellationCmd, MultiPassCmd, RayGunCmd, WarpCmd, WarpCmdChannels, WarpEvent,
-    WarpEventChannels,
+    ConstellationCmd, FileTransferProgress, FileTransferStep, FolderUploadSummary, MultiPassCmd,
+    OtherCmd, RayGunCmd, TesseractCmd, WarpCmd, WarpCmdChannels, WarpEvent, WarpEventChannels,
 };
 use std::collections::HashMap;
 use uuid::{uuid, Uuid};
 use warp::crypto::DID;
+use warp::multipass::identity::IdentityStatus;
 mod state;
 use crate::state::friends;
-use crate::state::storage;
+use crate::state::storage::{self, FileCategory, FileSearchResult, ItemStat, StorageStats};
 use crate::state::Chat;
 use once_cell::sync::Lazy;
 use state::State;
 use std::sync::Arc;
 mod testing;
 use ::function_name::named;
+use chrono::{DateTime, Utc};
 use clap::Parser;
 use std::path::PathBuf;
 
+use fluent_templates::static_loader;
+
+static_loader! {
+    static LOCALES = {
+        locales: "./locales",
+        fallback_language: "en-US",
+        customise: |bundle| bundle.set_use_isolating(false),
+    };
+}
+
+// how often the user's status is recomputed, e.g. to go Away when idle, and auto-lock is checked
+const INACTIVITY_INTERVAL: Duration = Duration::from_secs(15);
+// how often items past the retention period are removed from the trash
+const TRASH_PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 // ---- START WARP REQS
 pub static WARP_CMD_CH: Lazy<WarpCmdChannels> = Lazy::new(|| {
     let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
@@ -87,6 +118,11 @@ pub struct StaticArgs {
     pub id_path: PathBuf,
     pub experimental: bool,
     pub login_config_path: PathBuf,
+    pub link_preview_cache_path: PathBuf,
+    pub archives_path: PathBuf,
+    pub thumbnail_cache_path: PathBuf,
+    pub content_index_path: PathBuf,
+    pub trash_index_path: PathBuf,
 }
 #[derive(Debug, Parser)]
 #[clap(name = "")]
@@ -127,6 +163,11 @@ pub static STATIC_ARGS: Lazy<StaticArgs> = Lazy::new(|| {
         use_mock: args.no_mock, // remove the ! to disable mock data
         experimental: args.experimental_node,
         login_config_path: light_path.join("login_config.json"),
+        link_preview_cache_path: light_path.join("link_previews"),
+        archives_path: light_path.join("archives"),
+        thumbnail_cache_path: light_path.join("thumbnails"),
+        content_index_path: light_path.join("content_index.json"),
+        trash_index_path: light_path.join("trash.json"),
     }
 });
 // --- END WARP REQS
@@ -142,19 +183,39 @@ impl State {
         bool_val_one: Option<bool>,
         int_val_one: Option<i32>,
     ) -> state::State {
+        // every command comes from the user doing something, so it counts as activity for auto-lock
+        self.record_activity();
         // CREATE IDENTITY
         if command == "create_identity_command" {
             let handle = Handle::current();
 
-            let (tx, rx): (Sender<bool>, Receiver<bool>) = channel();
+            let (tx, rx): (Sender<Option<String>>, Receiver<Option<String>>) = channel();
             handle.spawn(async move {
                 // string_val_one == username, string_val_two == password
                 let outcome =
                     create_identity(string_val_one.unwrap(), string_val_two.unwrap()).await;
                 tx.send(outcome).unwrap();
             });
-            self.identity_exists = rx.recv().unwrap();
+            let mnemonic = rx.recv().unwrap();
+            self.identity_exists = mnemonic.is_some();
+            self.recovery_mnemonic = mnemonic;
             self.logged_in = true;
+        }
+        // RECOVER IDENTITY
+        else if command == "recover_identity_command" {
+            let handle = Handle::current();
+
+            let (tx, rx): (Sender<bool>, Receiver<bool>) = channel();
+            handle.spawn(async move {
+                // string_val_one == recovery phrase, string_val_two == new password
+                let outcome =
+                    recover_identity(string_val_one.unwrap(), string_val_two.unwrap()).await;
+                tx.send(outcome).unwrap();
+            });
+            if rx.recv().unwrap() {
+                self.identity_exists = true;
+                self.logged_in = true;
+            }
         } else if command == "increment_counter_command" {
             self.counter = self.counter + int_val_one.unwrap();
         } else if command == "login_command"
@@ -206,6 +267,7 @@ impl State {
                 true => {
                     println!("Login successful.");
                     self.logged_in = true;
+                    self.unlock();
                 }
                 // todo: notify user
                 false => {
@@ -236,9 +298,33 @@ impl State {
         }
         // DELETE IDENTITY
         else if command == "delete_identity_command" {
-            std::fs::remove_dir_all(dirs::home_dir().unwrap_or_default().join(".light"));
-            println!("{:?}", dirs::home_dir().unwrap_or_default().join(".light"));
-            std::process::abort();
+            let handle = Handle::current();
+            let (tx, rx): (
+                Sender<Result<DeletionReport, Error>>,
+                Receiver<Result<DeletionReport, Error>>,
+            ) = channel();
+            handle.spawn(async move {
+                // string_val_one == password, bool_val_one == keep an encrypted backup
+                let outcome = delete_account(
+                    string_val_one.unwrap_or_default(),
+                    bool_val_one.unwrap_or_default(),
+                )
+                .await;
+                tx.send(outcome).unwrap();
+            });
+            match rx.recv().unwrap() {
+                // the account is gone. start over as if Uplink was opened for the first time
+                Ok(report) => {
+                    self = state::State::default();
+                    self.deletion_report = Some(report);
+                }
+                // wrong passphrase or the backup couldn't be made. warp is still running with the account
+                Err(e) => {
+                    log::error!("failed to delete account: {e}");
+                    self.deletion_report = None;
+                    self.deletion_error = Some(e.to_string());
+                }
+            }
         }
         // SEND FRIEND REQUEST
         else if command == "send_friend_request_command" {
@@ -290,6 +376,140 @@ impl State {
                 let outcome_two = send_message(message, conv_id_uuid.unwrap()).await;
             });
         }
+        // IMPORT ARCHIVE
+        else if command == "import_archive_command" {
+            let handle = Handle::current();
+            let (tx, rx): (
+                Sender<Result<state::chats::ArchivedChat, Error>>,
+                Receiver<Result<state::chats::ArchivedChat, Error>>,
+            ) = channel();
+            handle.spawn(async move {
+                // string_val_one == archive path, string_val_two == attachments zip path
+                let outcome = import_archive(string_val_one.unwrap(), string_val_two).await;
+                tx.send(outcome).unwrap();
+            });
+            match rx.recv().unwrap() {
+                Ok(archived) => self.add_archived_chat(archived),
+                // todo: notify user
+                Err(e) => log::error!("failed to import archive: {e}"),
+            }
+        } else if command == "remove_archived_chat_command" {
+            // string_val_one == conv_id
+            if let Ok(id) = Uuid::parse_str(&string_val_one.unwrap()) {
+                self.remove_archived_chat(id);
+            }
+        }
+        // MESSAGE REQUESTS
+        else if command == "accept_message_request_command" {
+            // string_val_one == conv_id
+            if let Ok(id) = Uuid::parse_str(&string_val_one.unwrap()) {
+                self.mutate(state::Action::AcceptMessageRequest(id));
+            }
+        } else if command == "delete_message_request_command" {
+            // string_val_one == conv_id, bool_val_one == also block the other participants
+            if let Ok(id) = Uuid::parse_str(&string_val_one.unwrap()) {
+                let own_did = self.did_key();
+                let to_block: Vec<DID> = match self.chats().all.get(&id) {
+                    Some(chat) if bool_val_one.unwrap_or(false) => chat
+                        .participants
+                        .iter()
+                        .filter(|did| **did != own_did)
+                        .cloned()
+                        .collect(),
+                    _ => vec![],
+                };
+                let handle = Handle::current();
+                let (tx, rx): (Sender<Result<(), Error>>, Receiver<Result<(), Error>>) = channel();
+                handle.spawn(async move {
+                    let outcome = delete_message_request(id, to_block).await;
+                    tx.send(outcome).unwrap();
+                });
+                match rx.recv().unwrap() {
+                    Ok(_) => self.mutate(state::Action::RemoveMessageRequest(id)),
+                    // todo: notify user
+                    Err(e) => log::error!("failed to delete message request: {e}"),
+                }
+            }
+        }
+        // SAFETY NUMBERS
+        else if command == "set_contact_verified_command" {
+            // string_val_one == did_key, bool_val_one == verified
+            match DID::from_str(&string_val_one.unwrap()) {
+                Ok(did) => self.set_verified(did, bool_val_one.unwrap()),
+                Err(e) => log::error!("invalid did: {e}"),
+            }
+        }
+        // CONTACT INFO
+        // string_val_one == did_key, string_val_two == nickname, note, or tag
+        else if command == "set_contact_nickname_command"
+            || command == "set_contact_note_command"
+            || command == "add_contact_tag_command"
+            || command == "remove_contact_tag_command"
+        {
+            match DID::from_str(&string_val_one.unwrap()) {
+                Ok(did) => {
+                    let value = string_val_two.unwrap_or_default();
+                    let action = match command.as_str() {
+                        "set_contact_nickname_command" => {
+                            state::Action::SetNickname(&did, Some(value))
+                        }
+                        "set_contact_note_command" => state::Action::SetContactNote(&did, value),
+                        "add_contact_tag_command" => state::Action::AddContactTag(&did, value),
+                        _ => state::Action::RemoveContactTag(&did, value),
+                    };
+                    self.mutate(action);
+                }
+                Err(e) => log::error!("invalid did: {e}"),
+            }
+        }
+        // LINK PREVIEWS
+        else if command == "set_link_previews_enabled_command" {
+            // bool_val_one == enabled
+            self.mutate(state::Action::Config(
+                state::action::ConfigAction::SetLinkPreviewsEnabled(bool_val_one.unwrap()),
+            ));
+        }
+        // PRESENCE
+        else if command == "set_manual_status_command" {
+            // string_val_one == online, away, busy, or offline. None goes back to the automatic status
+            let status = match string_val_one.as_deref() {
+                Some("online") => Some(IdentityStatus::Online),
+                Some("away") => Some(IdentityStatus::Away),
+                Some("busy") => Some(IdentityStatus::Busy),
+                Some("offline") => Some(IdentityStatus::Offline),
+                _ => None,
+            };
+            self.mutate(state::Action::SetManualStatus(status));
+            publish_presence(&mut self);
+        } else if command == "set_auto_lock_timeout_command" {
+            // int_val_one == minutes. 0 disables auto-lock
+            let minutes = int_val_one.unwrap_or_default().max(0) as u64;
+            self.mutate(state::Action::Config(
+                state::action::ConfigAction::SetAutoLockTimeout(minutes),
+            ));
+        } else if command == "set_away_timeout_command" {
+            // int_val_one == minutes. 0 disables going Away automatically
+            let minutes = int_val_one.unwrap_or_default().max(0) as u64;
+            self.mutate(state::Action::Config(
+                state::action::ConfigAction::SetAwayTimeout(minutes),
+            ));
+            publish_presence(&mut self);
+        }
+        // FILES
+        else if command == "set_trash_retention_command" {
+            // int_val_one == days. 0 keeps deleted items until the trash is emptied
+            let days = int_val_one.unwrap_or_default().max(0) as u64;
+            self.mutate(state::Action::Config(
+                state::action::ConfigAction::SetTrashRetention(days),
+            ));
+        } else if command == "set_duplicate_upload_action_command" {
+            // string_val_one == keep_both or skip. None asks each time
+            let action = string_val_one
+                .and_then(|s| serde_json::from_value(serde_json::Value::String(s)).ok());
+            self.mutate(state::Action::Config(
+                state::action::ConfigAction::SetDuplicateUploadAction(action),
+            ));
+        }
 
         {
             state(self)
@@ -332,6 +552,7 @@ async fn main() {
     let state = Arc::new(Mutex::new(Some(state::State::load())));
 
     let state_clone = state.clone();
+    let state_inactivity = state.clone();
 
     let handle_warp_runner = || {
         let handle = Handle::current();
@@ -375,8 +596,50 @@ async fn main() {
 
             app_handle.run_on_main_thread(handle_warp_events);
 
+            let app_handle_ref = app.app_handle();
+            let state = state_inactivity;
+            let handle_inactivity = move || {
+                let handle = Handle::current();
+                handle.spawn(async move {
+                    let mut last_trash_purge: Option<Instant> = None;
+                    loop {
+                        sleep(INACTIVITY_INTERVAL).await;
+                        // the state is None while a command is running. it will be checked again on the next tick
+                        let locked = match state.lock().unwrap().as_mut() {
+                            Some(model) => {
+                                publish_presence(model);
+                                let purge_due = last_trash_purge
+                                    .map(|t| t.elapsed() >= TRASH_PURGE_INTERVAL)
+                                    .unwrap_or(true);
+                                if model.logged_in && purge_due {
+                                    purge_trash(model.configuration.files.trash_retention_days);
+                                    last_trash_purge = Some(Instant::now());
+                                }
+                                model.check_auto_lock()
+                            }
+                            None => false,
+                        };
+                        if locked {
+                            lock_tesseract();
+                            app_handle_ref.emit_all("warp-event", &state).unwrap();
+                        }
+                    }
+                });
+            };
+
+            app_handle.run_on_main_thread(handle_inactivity);
+
             Ok(())
         })
+        .on_window_event(|event| {
+            if let tauri::WindowEvent::Focused(focused) = event.event() {
+                let state = event.window().state::<StateState>();
+                if let Some(model) = state.0.lock().unwrap().as_mut() {
+                    model.set_window_focused(*focused);
+                    publish_presence(model);
+                };
+            }
+        })
         .manage(StateState(state))
         .invoke_handler(tauri::generate_handler![
             start_sam_command,
@@ -384,11 +647,51 @@ async fn main() {
             increment_counter_command,
             login_command,
             create_identity_command,
+            recover_identity_command,
+            take_recovery_mnemonic_command,
+            check_passphrase_command,
             delete_identity_command,
             get_own_did_key_command,
             send_friend_request_command,
             send_initial_message_command,
             send_message_command,
+            set_link_previews_enabled_command,
+            download_attachment_command,
+            download_directory_command,
+            cancel_download_command,
+            get_downloaded_attachment_command,
+            export_conversation_command,
+            import_archive_command,
+            remove_archived_chat_command,
+            accept_message_request_command,
+            delete_message_request_command,
+            get_archived_attachment_command,
+            get_safety_number_command,
+            set_contact_verified_command,
+            set_contact_nickname_command,
+            set_contact_note_command,
+            add_contact_tag_command,
+            remove_contact_tag_command,
+            get_friends_by_tag_command,
+            set_manual_status_command,
+            set_away_timeout_command,
+            lookup_identity_command,
+            upload_folder_command,
+            find_duplicates_command,
+            list_trash_command,
+            restore_items_command,
+            empty_trash_command,
+            set_trash_retention_command,
+            set_duplicate_upload_action_command,
+            list_directory_command,
+            stat_item_command,
+            get_thumbnail_command,
+            storage_stats_command,
+            search_files_command,
+            move_items_command,
+            copy_items_command,
+            report_activity_command,
+            set_auto_lock_timeout_command,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -439,9 +742,10 @@ async fn initialize_files() -> state::storage::Storage {
     let warp_cmd_tx = WARP_CMD_CH.tx.clone();
     let (tx, rx) = oneshot::channel::<Result<storage::Storage, warp::error::Error>>();
     warp_cmd_tx
-        .send(WarpCmd::Constellation(
-            ConstellationCmd::GetItemsFromCurrentDirectory { rsp: tx },
-        ))
+        .send(WarpCmd::Constellation(ConstellationCmd::ListDirectory {
+            path: "/".into(),
+            rsp: tx,
+        }))
         .expect("main failed to send warp command");
 
     let res = rx.await.expect("failed to get response from warp_runner");
@@ -467,6 +771,69 @@ async fn send_friend_request(did_key: String) -> bool {
     true
 }
 
+// blocks first, so that nothing else arrives from them while the conversation is deleted
+async fn delete_message_request(conv_id: Uuid, to_block: Vec<DID>) -> Result<(), Error> {
+    let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+    for did in to_block {
+        let (tx, rx) = oneshot::channel::<Result<(), warp::error::Error>>();
+        warp_cmd_tx
+            .send(WarpCmd::MultiPass(MultiPassCmd::Block { did, rsp: tx }))
+            .expect("main failed to send warp command");
+        rx.await.expect("failed to get response from warp_runner")?;
+    }
+
+    let (tx, rx) = oneshot::channel::<Result<(), warp::error::Error>>();
+    warp_cmd_tx
+        .send(WarpCmd::RayGun(RayGunCmd::DeleteConversation {
+            conv_id,
+            rsp: tx,
+        }))
+        .expect("main failed to send warp command");
+    rx.await.expect("failed to get response from warp_runner")
+}
+
+async fn upload_folder(
+    destination: String,
+    folder_path: PathBuf,
+    on_duplicate: DuplicateAction,
+    window: tauri::Window,
+) -> Result<FolderUploadSummary, Error> {
+    let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    warp_cmd_tx
+        .send(WarpCmd::Constellation(ConstellationCmd::UploadFolder {
+            path: destination,
+            folder_path,
+            on_duplicate,
+            rsp: tx,
+        }))
+        .expect("main failed to send warp command");
+    while let Some(msg) = rx.recv().await {
+        match msg {
+            FileTransferProgress::Step(FileTransferStep::Folder(progress)) => {
+                let _ = window.emit("folder-upload-progress", progress);
+            }
+            FileTransferProgress::Step(_) => {}
+            FileTransferProgress::Finished(summary) => return Ok(summary),
+            FileTransferProgress::Error(e) => return Err(e),
+        }
+    }
+    // warp_runner dropped the channel without finishing
+    Err(Error::Other)
+}
+
+async fn lookup_identity(query: String) -> Result<Vec<state::Identity>, warp::error::Error> {
+    let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+    let (tx, rx) = oneshot::channel::<Result<Vec<state::Identity>, warp::error::Error>>();
+    warp_cmd_tx
+        .send(WarpCmd::MultiPass(MultiPassCmd::LookupIdentity {
+            query,
+            rsp: tx,
+        }))
+        .expect("main failed to send warp command");
+    rx.await.expect("failed to get response from warp_runner")
+}
+
 async fn create_conversation(did_key: String) -> Result<ChatAdapter, warp::error::Error> {
     let warp_cmd_tx = WARP_CMD_CH.tx.clone();
     let (tx, rx) = oneshot::channel::<Result<ChatAdapter, _>>();
@@ -594,6 +961,41 @@ fn create_identity_command(
     return model_clone;
 }
 
+#[named]
+#[tauri::command]
+fn recover_identity_command(
+    mnemonic: String,
+    password: String,
+    state: tauri::State<StateState>,
+) -> state::State {
+    let mut model = state.0.lock().unwrap().take().unwrap();
+    let model = model.accept(
+        function_name!().to_string(),
+        Some(mnemonic),
+        Some(password),
+        None,
+        None,
+    );
+
+    let mut state_guard = state.0.lock().unwrap();
+    let model_clone = model.clone();
+    *state_guard = Some(model);
+    return model_clone;
+}
+
+// feedback contains keys from the locales folder
+#[tauri::command]
+fn check_passphrase_command(passphrase: String) -> PassphraseStrength {
+    passphrase::check(&passphrase, &passphrase::PassphrasePolicy::load())
+}
+
+// the recovery phrase is only returned once, after the account is created
+#[tauri::command]
+fn take_recovery_mnemonic_command(state: tauri::State<StateState>) -> Option<String> {
+    let mut state_guard = state.0.lock().unwrap();
+    state_guard.as_mut()?.recovery_mnemonic.take()
+}
+
 #[named]
 #[tauri::command]
 fn increment_counter_command(step: i32, state: tauri::State<StateState>) -> state::State {
@@ -608,9 +1010,19 @@ fn increment_counter_command(step: i32, state: tauri::State<StateState>) -> stat
 
 #[named]
 #[tauri::command]
-fn delete_identity_command(state: tauri::State<StateState>) -> state::State {
+fn delete_identity_command(
+    password: String,
+    keep_backup: bool,
+    state: tauri::State<StateState>,
+) -> state::State {
     let mut model = state.0.lock().unwrap().take().unwrap();
-    let model = model.accept(function_name!().to_string(), None, None, None, None);
+    let model = model.accept(
+        function_name!().to_string(),
+        Some(password),
+        None,
+        Some(keep_backup),
+        None,
+    );
 
     let mut state_guard = state.0.lock().unwrap();
     let model_clone = model.clone();
@@ -683,74 +1095,1062 @@ fn send_message_command(
     return model_clone;
 }
 
-async fn try_login(passphrase: String) -> Result<bool, Error> {
-    // Try Login
-    let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+#[named]
+#[tauri::command]
+fn set_link_previews_enabled_command(
+    enabled: bool,
+    state: tauri::State<StateState>,
+) -> state::State {
+    let mut model = state.0.lock().unwrap().take().unwrap();
+    let model = model.accept(
+        function_name!().to_string(),
+        None,
+        None,
+        Some(enabled),
+        None,
+    );
 
-    let (tx, rx) =
-        oneshot::channel::<Result<warp::multipass::identity::Identity, warp::error::Error>>();
-    warp_cmd_tx
-        .send(WarpCmd::MultiPass(MultiPassCmd::TryLogIn {
-            passphrase,
-            rsp: tx,
-        }))
-        .expect("UnlockLayout failed to send warp command");
+    let mut state_guard = state.0.lock().unwrap();
+    let model_clone = model.clone();
+    *state_guard = Some(model);
+    return model_clone;
+}
 
-    let res = rx.await.expect("failed to get response from warp_runner");
+// returns the id of the queued download, or an empty string if it couldn't be started.
+// progress is tracked in state.downloads
+#[tauri::command]
+fn download_attachment_command(conv_id: String, msg_id: String, file_name: String) -> String {
+    let handle = Handle::current();
+    let (tx, rx): (Sender<String>, Receiver<String>) = channel();
+    handle.spawn(async move {
+        let outcome = download_attachment(conv_id, msg_id, file_name).await;
+        tx.send(outcome.map(|id| id.to_string()).unwrap_or_default())
+            .unwrap();
+    });
+    rx.recv().unwrap()
+}
 
-    match res {
-        Ok(_) => {
-            println!("Login worked");
-            Ok(true)
-        }
-        // todo: notify user
-        Err(e) => {
-            println!("Login Failed {:?}", e);
-            Err(e)
+// path starts at the root directory, e.g. "/photos". the zip file is saved in the downloads directory. returns the
+// id of the queued download, or an empty string if it couldn't be started
+#[tauri::command]
+fn download_directory_command(path: String) -> String {
+    let handle = Handle::current();
+    let (tx, rx): (Sender<String>, Receiver<String>) = channel();
+    handle.spawn(async move {
+        let outcome = download_directory(path).await;
+        if let Err(e) = &outcome {
+            log::error!("failed to download directory: {e}");
         }
-    }
+        tx.send(outcome.map(|id| id.to_string()).unwrap_or_default())
+            .unwrap();
+    });
+    rx.recv().unwrap()
 }
 
-async fn send_own_did_key_to_front_end() -> Result<String, Error> {
-    let warp_cmd_tx = WARP_CMD_CH.tx.clone();
-    // Get own did:key
-    let (tx, rx) = oneshot::channel::<Result<DID, warp::error::Error>>();
-    if let Err(e) = warp_cmd_tx.send(WarpCmd::MultiPass(MultiPassCmd::GetOwnDid { rsp: tx })) {
-        log::error!("failed to send warp command: {}", e);
+#[tauri::command]
+fn cancel_download_command(download_id: String) -> bool {
+    match Uuid::parse_str(&download_id) {
+        Ok(id) => warp_runner::downloads::cancel(id),
+        Err(_) => false,
     }
+}
 
-    let res = rx.await.expect("failed to get response from warp_runner");
+// returns where the attachment was saved, if it has already been downloaded
+#[tauri::command]
+fn get_downloaded_attachment_command(
+    msg_id: String,
+    file_name: String,
+    state: tauri::State<StateState>,
+) -> Option<String> {
+    let msg_id = Uuid::parse_str(&msg_id).ok()?;
+    let state_guard = state.0.lock().unwrap();
+    state_guard
+        .as_ref()?
+        .downloads
+        .get_completed(msg_id, &file_name)
+        .map(|path| path.to_string_lossy().to_string())
+}
 
-    match res {
-        Ok(_) => Ok(res.unwrap().to_string()),
-        // todo: notify user
-        Err(e) => Err(e),
-    }
+async fn download_attachment(
+    conv_id: String,
+    msg_id: String,
+    file_name: String,
+) -> Result<Uuid, Error> {
+    let to_err = |e: uuid::Error| Error::OtherWithContext(e.to_string());
+    let directory =
+        dirs::download_dir().unwrap_or_else(|| STATIC_ARGS.light_path.join("downloads"));
+    let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+    let (tx, rx) = oneshot::channel::<Result<Uuid, warp::error::Error>>();
+    warp_cmd_tx
+        .send(WarpCmd::RayGun(RayGunCmd::DownloadAttachment {
+            conv_id: Uuid::parse_str(&conv_id).map_err(to_err)?,
+            msg_id: Uuid::parse_str(&msg_id).map_err(to_err)?,
+            file_name,
+            directory,
+            rsp: tx,
+        }))
+        .expect("main failed to send warp command");
+    rx.await.expect("failed to get response from warp_runner")
 }
 
-async fn create_identity(username: String, passphrase: String) -> bool {
-    // Create Identity
-    let (tx, rx) =
-        oneshot::channel::<Result<warp::multipass::identity::Identity, warp::error::Error>>();
+async fn download_directory(path: String) -> Result<Uuid, Error> {
+    let directory =
+        dirs::download_dir().unwrap_or_else(|| STATIC_ARGS.light_path.join("downloads"));
+    let name = match path.trim_matches('/').rsplit('/').next() {
+        Some(name) if !name.is_empty() => name.to_string(),
+        _ => "storage".to_string(),
+    };
+    let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+    let (tx, rx) = oneshot::channel::<Result<Uuid, warp::error::Error>>();
+    warp_cmd_tx
+        .send(WarpCmd::Constellation(
+            ConstellationCmd::DownloadDirectory {
+                path,
+                dest: directory.join(format!("{name}.zip")),
+                rsp: tx,
+            },
+        ))
+        .expect("main failed to send warp command");
+    rx.await.expect("failed to get response from warp_runner")
+}
+
+// format is one of "json", "md", or "html". only messages sent between `from` and `to` are exported, leaving both
+// out exports the whole conversation. returns the path of the exported file, or an empty string on failure
+#[tauri::command]
+fn export_conversation_command(
+    conv_id: String,
+    format: String,
+    include_attachments: bool,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> String {
+    let range = match (from, to) {
+        (None, None) => None,
+        (from, to) => Some(
+            from.unwrap_or_else(|| DateTime::<Utc>::from(std::time::UNIX_EPOCH))
+                ..to.unwrap_or_else(Utc::now),
+        ),
+    };
+    let handle = Handle::current();
+    let (tx, rx): (Sender<String>, Receiver<String>) = channel();
+    handle.spawn(async move {
+        let outcome = export_conversation(conv_id, format, range, include_attachments).await;
+        if let Err(e) = &outcome {
+            log::error!("failed to export conversation: {e}");
+        }
+        tx.send(
+            outcome
+                .map(|path| path.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        )
+        .unwrap();
+    });
+    rx.recv().unwrap()
+}
 
+async fn export_conversation(
+    conv_id: String,
+    format: String,
+    range: ExportRange,
+    include_attachments: bool,
+) -> Result<PathBuf, Error> {
+    let format = match format.as_str() {
+        "json" => ExportFormat::Json,
+        "md" => ExportFormat::Markdown,
+        "html" => ExportFormat::Html,
+        _ => return Err(Error::OtherWithContext(format!("unknown format: {format}"))),
+    };
+    let directory = dirs::download_dir().unwrap_or_else(|| STATIC_ARGS.light_path.join("exports"));
     let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+    let (tx, rx) = oneshot::channel::<Result<PathBuf, warp::error::Error>>();
     warp_cmd_tx
-        .send(WarpCmd::MultiPass(MultiPassCmd::CreateIdentity {
-            username,
-            passphrase,
+        .send(WarpCmd::RayGun(RayGunCmd::ExportConversation {
+            conv_id: Uuid::parse_str(&conv_id)
+                .map_err(|e| Error::OtherWithContext(e.to_string()))?,
+            format,
+            range,
+            include_attachments,
+            directory,
             rsp: tx,
         }))
-        .expect("UnlockLayout failed to send warp command");
+        .expect("main failed to send warp command");
+    rx.await.expect("failed to get response from warp_runner")
+}
 
-    let res = rx.await.expect("failed to get response from warp_runner");
+#[named]
+#[tauri::command]
+fn import_archive_command(
+    archive_path: String,
+    attachments_path: Option<String>,
+    state: tauri::State<StateState>,
+) -> state::State {
+    let mut model = state.0.lock().unwrap().take().unwrap();
+    let model = model.accept(
+        function_name!().to_string(),
+        Some(archive_path),
+        attachments_path,
+        None,
+        None,
+    );
 
-    match res {
-        Ok(_) => {
-            println!("Create identity successful.");
-            true
-        }
-        Err(e) => {
-            println!("Create identity failed {:?}", e);
+    let mut state_guard = state.0.lock().unwrap();
+    let model_clone = model.clone();
+    *state_guard = Some(model);
+    return model_clone;
+}
+
+#[named]
+#[tauri::command]
+fn remove_archived_chat_command(conv_id: String, state: tauri::State<StateState>) -> state::State {
+    let mut model = state.0.lock().unwrap().take().unwrap();
+    let model = model.accept(
+        function_name!().to_string(),
+        Some(conv_id),
+        None,
+        None,
+        None,
+    );
+
+    let mut state_guard = state.0.lock().unwrap();
+    let model_clone = model.clone();
+    *state_guard = Some(model);
+    return model_clone;
+}
+
+#[named]
+#[tauri::command]
+fn accept_message_request_command(
+    conv_id: String,
+    state: tauri::State<StateState>,
+) -> state::State {
+    let mut model = state.0.lock().unwrap().take().unwrap();
+    let model = model.accept(
+        function_name!().to_string(),
+        Some(conv_id),
+        None,
+        None,
+        None,
+    );
+
+    let mut state_guard = state.0.lock().unwrap();
+    let model_clone = model.clone();
+    *state_guard = Some(model);
+    return model_clone;
+}
+
+// `block`: also blocks the other participants
+#[named]
+#[tauri::command]
+fn delete_message_request_command(
+    conv_id: String,
+    block: bool,
+    state: tauri::State<StateState>,
+) -> state::State {
+    let mut model = state.0.lock().unwrap().take().unwrap();
+    let model = model.accept(
+        function_name!().to_string(),
+        Some(conv_id),
+        None,
+        Some(block),
+        None,
+    );
+
+    let mut state_guard = state.0.lock().unwrap();
+    let model_clone = model.clone();
+    *state_guard = Some(model);
+    return model_clone;
+}
+
+// returns the location of an attachment from an imported archive, if it was included in the archive
+#[tauri::command]
+fn get_archived_attachment_command(
+    conv_id: String,
+    msg_id: String,
+    file_name: String,
+    state: tauri::State<StateState>,
+) -> Option<String> {
+    let conv_id = Uuid::parse_str(&conv_id).ok()?;
+    let msg_id = Uuid::parse_str(&msg_id).ok()?;
+    let state_guard = state.0.lock().unwrap();
+    state_guard
+        .as_ref()?
+        .chats
+        .archived
+        .get(&conv_id)?
+        .attachment_path(msg_id, &file_name)
+        .map(|path| path.to_string_lossy().to_string())
+}
+
+async fn import_archive(
+    archive_path: String,
+    attachments_path: Option<String>,
+) -> Result<state::chats::ArchivedChat, Error> {
+    let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+    let (tx, rx) = oneshot::channel::<Result<state::chats::ArchivedChat, warp::error::Error>>();
+    warp_cmd_tx
+        .send(WarpCmd::Other(OtherCmd::ImportArchive {
+            archive: PathBuf::from(archive_path),
+            attachments: attachments_path.map(PathBuf::from),
+            rsp: tx,
+        }))
+        .expect("main failed to send warp command");
+    rx.await.expect("failed to get response from warp_runner")
+}
+
+#[tauri::command]
+fn get_safety_number_command(
+    did_key: String,
+    state: tauri::State<StateState>,
+) -> Option<SafetyNumber> {
+    let did = DID::from_str(&did_key).ok()?;
+    let state_guard = state.0.lock().unwrap();
+    Some(state_guard.as_ref()?.safety_number(&did))
+}
+
+#[named]
+#[tauri::command]
+fn set_contact_verified_command(
+    did_key: String,
+    verified: bool,
+    state: tauri::State<StateState>,
+) -> state::State {
+    let mut model = state.0.lock().unwrap().take().unwrap();
+    let model = model.accept(
+        function_name!().to_string(),
+        Some(did_key),
+        None,
+        Some(verified),
+        None,
+    );
+
+    let mut state_guard = state.0.lock().unwrap();
+    let model_clone = model.clone();
+    *state_guard = Some(model);
+    return model_clone;
+}
+
+#[named]
+#[tauri::command]
+fn set_contact_nickname_command(
+    did_key: String,
+    nickname: String,
+    state: tauri::State<StateState>,
+) -> state::State {
+    let mut model = state.0.lock().unwrap().take().unwrap();
+    let model = model.accept(
+        function_name!().to_string(),
+        Some(did_key),
+        Some(nickname),
+        None,
+        None,
+    );
+
+    let mut state_guard = state.0.lock().unwrap();
+    let model_clone = model.clone();
+    *state_guard = Some(model);
+    return model_clone;
+}
+
+#[named]
+#[tauri::command]
+fn set_contact_note_command(
+    did_key: String,
+    note: String,
+    state: tauri::State<StateState>,
+) -> state::State {
+    let mut model = state.0.lock().unwrap().take().unwrap();
+    let model = model.accept(
+        function_name!().to_string(),
+        Some(did_key),
+        Some(note),
+        None,
+        None,
+    );
+
+    let mut state_guard = state.0.lock().unwrap();
+    let model_clone = model.clone();
+    *state_guard = Some(model);
+    return model_clone;
+}
+
+#[named]
+#[tauri::command]
+fn add_contact_tag_command(
+    did_key: String,
+    tag: String,
+    state: tauri::State<StateState>,
+) -> state::State {
+    let mut model = state.0.lock().unwrap().take().unwrap();
+    let model = model.accept(
+        function_name!().to_string(),
+        Some(did_key),
+        Some(tag),
+        None,
+        None,
+    );
+
+    let mut state_guard = state.0.lock().unwrap();
+    let model_clone = model.clone();
+    *state_guard = Some(model);
+    return model_clone;
+}
+
+#[named]
+#[tauri::command]
+fn remove_contact_tag_command(
+    did_key: String,
+    tag: String,
+    state: tauri::State<StateState>,
+) -> state::State {
+    let mut model = state.0.lock().unwrap().take().unwrap();
+    let model = model.accept(
+        function_name!().to_string(),
+        Some(did_key),
+        Some(tag),
+        None,
+        None,
+    );
+
+    let mut state_guard = state.0.lock().unwrap();
+    let model_clone = model.clone();
+    *state_guard = Some(model);
+    return model_clone;
+}
+
+#[tauri::command]
+fn get_friends_by_tag_command(
+    tag: String,
+    state: tauri::State<StateState>,
+) -> Vec<state::Identity> {
+    let state_guard = state.0.lock().unwrap();
+    match state_guard.as_ref() {
+        Some(model) => model.get_friends_by_tag(&tag),
+        None => vec![],
+    }
+}
+
+#[named]
+#[tauri::command]
+fn set_manual_status_command(
+    status: Option<String>,
+    state: tauri::State<StateState>,
+) -> state::State {
+    let mut model = state.0.lock().unwrap().take().unwrap();
+    let model = model.accept(function_name!().to_string(), status, None, None, None);
+
+    let mut state_guard = state.0.lock().unwrap();
+    let model_clone = model.clone();
+    *state_guard = Some(model);
+    return model_clone;
+}
+
+#[named]
+#[tauri::command]
+fn set_away_timeout_command(minutes: i32, state: tauri::State<StateState>) -> state::State {
+    let mut model = state.0.lock().unwrap().take().unwrap();
+    let model = model.accept(
+        function_name!().to_string(),
+        None,
+        None,
+        None,
+        Some(minutes),
+    );
+
+    let mut state_guard = state.0.lock().unwrap();
+    let model_clone = model.clone();
+    *state_guard = Some(model);
+    return model_clone;
+}
+
+// query is a username, username#short_id, or did:key. send_friend_request_command takes the did_key of a result
+#[tauri::command]
+fn lookup_identity_command(query: String) -> Vec<state::Identity> {
+    let handle = Handle::current();
+    let (tx, rx): (Sender<Vec<state::Identity>>, Receiver<Vec<state::Identity>>) = channel();
+    handle.spawn(async move {
+        let outcome = match lookup_identity(query).await {
+            Ok(identities) => identities,
+            Err(e) => {
+                log::error!("failed to look up identity: {e}");
+                vec![]
+            }
+        };
+        tx.send(outcome).unwrap();
+    });
+    rx.recv().unwrap()
+}
+
+// uploads the local folder at `path` into the storage directory at `destination`, e.g. "/photos". progress is emitted
+// as "folder-upload-progress". on_duplicate is for files whose contents are already in storage. if None, the default
+// from the configuration is used, and if that isn't set both copies are kept
+#[tauri::command]
+fn upload_folder_command(
+    path: String,
+    destination: String,
+    on_duplicate: Option<DuplicateAction>,
+    window: tauri::Window,
+    state: tauri::State<StateState>,
+) -> Option<FolderUploadSummary> {
+    let on_duplicate = on_duplicate
+        .or_else(|| {
+            state
+                .0
+                .lock()
+                .unwrap()
+                .as_ref()
+                .and_then(|model| model.configuration.files.on_duplicate_upload)
+        })
+        .unwrap_or(DuplicateAction::KeepBoth);
+    let handle = Handle::current();
+    let (tx, rx): (
+        Sender<Result<FolderUploadSummary, Error>>,
+        Receiver<Result<FolderUploadSummary, Error>>,
+    ) = channel();
+    handle.spawn(async move {
+        let outcome = upload_folder(destination, PathBuf::from(path), on_duplicate, window).await;
+        tx.send(outcome).unwrap();
+    });
+    match rx.recv().unwrap() {
+        Ok(summary) => {
+            if let Some(model) = state.0.lock().unwrap().as_mut() {
+                model.storage = summary.storage.clone();
+            }
+            Some(summary)
+        }
+        Err(e) => {
+            log::error!("failed to upload folder: {e}");
+            None
+        }
+    }
+}
+
+// path starts at the root directory, e.g. "/photos/2020"
+#[tauri::command]
+fn list_directory_command(
+    path: String,
+    state: tauri::State<StateState>,
+) -> Option<storage::DirectoryListing> {
+    let handle = Handle::current();
+    let (tx, rx): (
+        Sender<Result<storage::Storage, Error>>,
+        Receiver<Result<storage::Storage, Error>>,
+    ) = channel();
+    handle.spawn(async move {
+        let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+        let (rsp, rx) = oneshot::channel::<Result<storage::Storage, warp::error::Error>>();
+        warp_cmd_tx
+            .send(WarpCmd::Constellation(ConstellationCmd::ListDirectory {
+                path,
+                rsp,
+            }))
+            .expect("main failed to send warp command");
+        let outcome = rx.await.expect("failed to get response from warp_runner");
+        tx.send(outcome).unwrap();
+    });
+    match rx.recv().unwrap() {
+        Ok(storage) => {
+            let listing = storage::DirectoryListing::from(&storage);
+            if let Some(model) = state.0.lock().unwrap().as_mut() {
+                model.storage = storage;
+            }
+            Some(listing)
+        }
+        Err(e) => {
+            log::error!("failed to list directory: {e}");
+            None
+        }
+    }
+}
+
+#[tauri::command]
+fn list_trash_command() -> Vec<TrashEntry> {
+    let handle = Handle::current();
+    let (tx, rx): (Sender<Vec<TrashEntry>>, Receiver<Vec<TrashEntry>>) = channel();
+    handle.spawn(async move {
+        let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+        let (rsp, rx) = oneshot::channel::<Result<Vec<TrashEntry>, warp::error::Error>>();
+        warp_cmd_tx
+            .send(WarpCmd::Constellation(ConstellationCmd::ListTrash { rsp }))
+            .expect("main failed to send warp command");
+        let outcome = match rx.await.expect("failed to get response from warp_runner") {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::error!("failed to list trash: {e}");
+                vec![]
+            }
+        };
+        tx.send(outcome).unwrap();
+    });
+    rx.recv().unwrap()
+}
+
+// ids are TrashEntry ids. list the directory being shown again afterwards, items may have been restored to it
+#[tauri::command]
+fn restore_items_command(ids: Vec<String>) -> bool {
+    let ids = ids
+        .iter()
+        .filter_map(|id| Uuid::parse_str(id).ok())
+        .collect::<Vec<_>>();
+    let handle = Handle::current();
+    let (tx, rx): (Sender<bool>, Receiver<bool>) = channel();
+    handle.spawn(async move {
+        let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+        let (rsp, rx) = oneshot::channel::<Result<(), warp::error::Error>>();
+        warp_cmd_tx
+            .send(WarpCmd::Constellation(ConstellationCmd::RestoreItems {
+                ids,
+                rsp,
+            }))
+            .expect("main failed to send warp command");
+        let outcome = match rx.await.expect("failed to get response from warp_runner") {
+            Ok(_) => true,
+            Err(e) => {
+                log::error!("failed to restore items: {e}");
+                false
+            }
+        };
+        tx.send(outcome).unwrap();
+    });
+    rx.recv().unwrap()
+}
+
+#[tauri::command]
+fn empty_trash_command() -> bool {
+    let handle = Handle::current();
+    let (tx, rx): (Sender<bool>, Receiver<bool>) = channel();
+    handle.spawn(async move {
+        let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+        let (rsp, rx) = oneshot::channel::<Result<(), warp::error::Error>>();
+        warp_cmd_tx
+            .send(WarpCmd::Constellation(ConstellationCmd::EmptyTrash { rsp }))
+            .expect("main failed to send warp command");
+        let outcome = match rx.await.expect("failed to get response from warp_runner") {
+            Ok(_) => true,
+            Err(e) => {
+                log::error!("failed to empty trash: {e}");
+                false
+            }
+        };
+        tx.send(outcome).unwrap();
+    });
+    rx.recv().unwrap()
+}
+
+#[named]
+#[tauri::command]
+fn set_trash_retention_command(days: i32, state: tauri::State<StateState>) -> state::State {
+    let mut model = state.0.lock().unwrap().take().unwrap();
+    let model = model.accept(function_name!().to_string(), None, None, None, Some(days));
+
+    let mut state_guard = state.0.lock().unwrap();
+    let model_clone = model.clone();
+    *state_guard = Some(model);
+    return model_clone;
+}
+
+// paths are local files or folders. call before uploading them to ask the user what to do with the duplicates,
+// unless the configuration already has a default
+#[tauri::command]
+fn find_duplicates_command(paths: Vec<String>) -> Vec<DuplicateUpload> {
+    let handle = Handle::current();
+    let (tx, rx): (Sender<Vec<DuplicateUpload>>, Receiver<Vec<DuplicateUpload>>) = channel();
+    handle.spawn(async move {
+        let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+        let (rsp, rx) = oneshot::channel::<Result<Vec<DuplicateUpload>, warp::error::Error>>();
+        warp_cmd_tx
+            .send(WarpCmd::Constellation(ConstellationCmd::FindDuplicates {
+                files_path: paths.into_iter().map(PathBuf::from).collect(),
+                rsp,
+            }))
+            .expect("main failed to send warp command");
+        let outcome = match rx.await.expect("failed to get response from warp_runner") {
+            Ok(duplicates) => duplicates,
+            Err(e) => {
+                log::error!("failed to find duplicates: {e}");
+                vec![]
+            }
+        };
+        tx.send(outcome).unwrap();
+    });
+    rx.recv().unwrap()
+}
+
+#[named]
+#[tauri::command]
+fn set_duplicate_upload_action_command(
+    action: Option<String>,
+    state: tauri::State<StateState>,
+) -> state::State {
+    let mut model = state.0.lock().unwrap().take().unwrap();
+    let model = model.accept(function_name!().to_string(), action, None, None, None);
+
+    let mut state_guard = state.0.lock().unwrap();
+    let model_clone = model.clone();
+    *state_guard = Some(model);
+    return model_clone;
+}
+
+#[tauri::command]
+fn stat_item_command(path: String) -> Option<ItemStat> {
+    let handle = Handle::current();
+    let (tx, rx): (Sender<Option<ItemStat>>, Receiver<Option<ItemStat>>) = channel();
+    handle.spawn(async move {
+        let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+        let (rsp, rx) = oneshot::channel::<Result<ItemStat, warp::error::Error>>();
+        warp_cmd_tx
+            .send(WarpCmd::Constellation(ConstellationCmd::Stat { path, rsp }))
+            .expect("main failed to send warp command");
+        let outcome = match rx.await.expect("failed to get response from warp_runner") {
+            Ok(stat) => Some(stat),
+            Err(e) => {
+                log::error!("failed to stat item: {e}");
+                None
+            }
+        };
+        tx.send(outcome).unwrap();
+    });
+    rx.recv().unwrap()
+}
+
+// returns a data url. size is small or large
+#[tauri::command]
+fn get_thumbnail_command(path: String, size: ThumbnailSize) -> Option<String> {
+    let handle = Handle::current();
+    let (tx, rx): (Sender<Option<String>>, Receiver<Option<String>>) = channel();
+    handle.spawn(async move {
+        let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+        let (rsp, rx) = oneshot::channel::<Result<String, warp::error::Error>>();
+        warp_cmd_tx
+            .send(WarpCmd::Constellation(ConstellationCmd::GetThumbnail {
+                path,
+                size,
+                rsp,
+            }))
+            .expect("main failed to send warp command");
+        let outcome = match rx.await.expect("failed to get response from warp_runner") {
+            Ok(thumbnail) => Some(thumbnail),
+            Err(e) => {
+                log::error!("failed to get thumbnail: {e}");
+                None
+            }
+        };
+        tx.send(outcome).unwrap();
+    });
+    rx.recv().unwrap()
+}
+
+// see SearchFilter in constellation_commands for how the query is matched. kind is image, video, document, or other
+#[tauri::command]
+fn search_files_command(
+    query: String,
+    kind: Option<FileCategory>,
+    min_size: Option<usize>,
+    modified_after: Option<DateTime<Utc>>,
+) -> Vec<FileSearchResult> {
+    let handle = Handle::current();
+    let (tx, rx): (
+        Sender<Vec<FileSearchResult>>,
+        Receiver<Vec<FileSearchResult>>,
+    ) = channel();
+    handle.spawn(async move {
+        let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+        let (rsp, rx) = oneshot::channel::<Result<Vec<FileSearchResult>, warp::error::Error>>();
+        warp_cmd_tx
+            .send(WarpCmd::Constellation(ConstellationCmd::SearchFiles {
+                query,
+                kind,
+                min_size,
+                modified_after,
+                rsp,
+            }))
+            .expect("main failed to send warp command");
+        let outcome = match rx.await.expect("failed to get response from warp_runner") {
+            Ok(results) => results,
+            Err(e) => {
+                log::error!("failed to search files: {e}");
+                vec![]
+            }
+        };
+        tx.send(outcome).unwrap();
+    });
+    rx.recv().unwrap()
+}
+
+#[tauri::command]
+fn storage_stats_command() -> Option<StorageStats> {
+    let handle = Handle::current();
+    let (tx, rx): (Sender<Option<StorageStats>>, Receiver<Option<StorageStats>>) = channel();
+    handle.spawn(async move {
+        let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+        let (rsp, rx) = oneshot::channel::<Result<StorageStats, warp::error::Error>>();
+        warp_cmd_tx
+            .send(WarpCmd::Constellation(ConstellationCmd::StorageStats {
+                rsp,
+            }))
+            .expect("main failed to send warp command");
+        let outcome = match rx.await.expect("failed to get response from warp_runner") {
+            Ok(stats) => Some(stats),
+            Err(e) => {
+                log::error!("failed to get storage stats: {e}");
+                None
+            }
+        };
+        tx.send(outcome).unwrap();
+    });
+    rx.recv().unwrap()
+}
+
+// source_paths and destination start at the root directory, e.g. "/photos/cat.png" and "/". returns the items which
+// couldn't be moved, with the reason, or None if nothing could be
+#[tauri::command]
+fn move_items_command(
+    source_paths: Vec<String>,
+    destination: String,
+    state: tauri::State<StateState>,
+) -> Option<Vec<(String, String)>> {
+    move_or_copy_items(source_paths, destination, false, state)
+}
+
+#[tauri::command]
+fn copy_items_command(
+    source_paths: Vec<String>,
+    destination: String,
+    state: tauri::State<StateState>,
+) -> Option<Vec<(String, String)>> {
+    move_or_copy_items(source_paths, destination, true, state)
+}
+
+fn move_or_copy_items(
+    source_paths: Vec<String>,
+    destination: String,
+    copy: bool,
+    state: tauri::State<StateState>,
+) -> Option<Vec<(String, String)>> {
+    let handle = Handle::current();
+    let (tx, rx): (
+        Sender<Result<(storage::Storage, Vec<(String, String)>), Error>>,
+        Receiver<Result<(storage::Storage, Vec<(String, String)>), Error>>,
+    ) = channel();
+    handle.spawn(async move {
+        let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+        let (rsp, rx) = oneshot::channel::<
+            Result<(storage::Storage, Vec<(String, String)>), warp::error::Error>,
+        >();
+        let cmd = if copy {
+            ConstellationCmd::CopyItems {
+                source_paths,
+                destination,
+                rsp,
+            }
+        } else {
+            ConstellationCmd::MoveItems {
+                source_paths,
+                destination,
+                rsp,
+            }
+        };
+        warp_cmd_tx
+            .send(WarpCmd::Constellation(cmd))
+            .expect("main failed to send warp command");
+        let outcome = rx.await.expect("failed to get response from warp_runner");
+        tx.send(outcome).unwrap();
+    });
+    match rx.recv().unwrap() {
+        Ok((storage, failed)) => {
+            if let Some(model) = state.0.lock().unwrap().as_mut() {
+                model.storage = storage;
+            }
+            Some(failed)
+        }
+        Err(e) => {
+            log::error!("failed to move or copy items: {e}");
+            None
+        }
+    }
+}
+
+// the UI calls this on user input, at most every few seconds. used by auto-lock
+#[tauri::command]
+fn report_activity_command(state: tauri::State<StateState>) {
+    if let Some(model) = state.0.lock().unwrap().as_mut() {
+        model.record_activity();
+    }
+}
+
+#[named]
+#[tauri::command]
+fn set_auto_lock_timeout_command(minutes: i32, state: tauri::State<StateState>) -> state::State {
+    let mut model = state.0.lock().unwrap().take().unwrap();
+    let model = model.accept(
+        function_name!().to_string(),
+        None,
+        None,
+        None,
+        Some(minutes),
+    );
+
+    let mut state_guard = state.0.lock().unwrap();
+    let model_clone = model.clone();
+    *state_guard = Some(model);
+    return model_clone;
+}
+
+fn purge_trash(retention_days: u64) {
+    let (tx, _rx) = oneshot::channel::<Result<(), warp::error::Error>>();
+    if let Err(e) = WARP_CMD_CH
+        .tx
+        .send(WarpCmd::Constellation(ConstellationCmd::PurgeTrash {
+            retention_days,
+            rsp: tx,
+        }))
+    {
+        log::error!("failed to send warp command: {e}");
+    }
+}
+
+fn lock_tesseract() {
+    let (tx, _rx) = oneshot::channel::<()>();
+    if let Err(e) = WARP_CMD_CH
+        .tx
+        .send(WarpCmd::Tesseract(TesseractCmd::Lock { rsp: tx }))
+    {
+        log::error!("failed to send warp command: {e}");
+    }
+}
+
+// recomputes the user's status and sends it to MultiPass if it changed
+fn publish_presence(model: &mut state::State) {
+    if !model.logged_in {
+        return;
+    }
+    if let Some(status) = model.update_presence() {
+        let (tx, _rx) = oneshot::channel::<Result<(), warp::error::Error>>();
+        if let Err(e) = WARP_CMD_CH
+            .tx
+            .send(WarpCmd::MultiPass(MultiPassCmd::SetIdentityStatus {
+                status,
+                rsp: tx,
+            }))
+        {
+            log::error!("failed to send warp command: {e}");
+        }
+    }
+}
+
+async fn try_login(passphrase: String) -> Result<bool, Error> {
+    // Try Login
+    let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+
+    let (tx, rx) =
+        oneshot::channel::<Result<warp::multipass::identity::Identity, warp::error::Error>>();
+    warp_cmd_tx
+        .send(WarpCmd::MultiPass(MultiPassCmd::TryLogIn {
+            passphrase,
+            rsp: tx,
+        }))
+        .expect("UnlockLayout failed to send warp command");
+
+    let res = rx.await.expect("failed to get response from warp_runner");
+
+    match res {
+        Ok(_) => {
+            println!("Login worked");
+            Ok(true)
+        }
+        // todo: notify user
+        Err(e) => {
+            println!("Login Failed {:?}", e);
+            Err(e)
+        }
+    }
+}
+
+async fn send_own_did_key_to_front_end() -> Result<String, Error> {
+    let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+    // Get own did:key
+    let (tx, rx) = oneshot::channel::<Result<DID, warp::error::Error>>();
+    if let Err(e) = warp_cmd_tx.send(WarpCmd::MultiPass(MultiPassCmd::GetOwnDid { rsp: tx })) {
+        log::error!("failed to send warp command: {}", e);
+    }
+
+    let res = rx.await.expect("failed to get response from warp_runner");
+
+    match res {
+        Ok(_) => Ok(res.unwrap().to_string()),
+        // todo: notify user
+        Err(e) => Err(e),
+    }
+}
+
+// returns the recovery phrase
+async fn create_identity(username: String, passphrase: String) -> Option<String> {
+    // Create Identity
+    let (tx, rx) = oneshot::channel::<
+        Result<(warp::multipass::identity::Identity, String), warp::error::Error>,
+    >();
+
+    let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+    warp_cmd_tx
+        .send(WarpCmd::MultiPass(MultiPassCmd::CreateIdentity {
+            username,
+            passphrase,
+            rsp: tx,
+        }))
+        .expect("UnlockLayout failed to send warp command");
+
+    let res = rx.await.expect("failed to get response from warp_runner");
+
+    match res {
+        Ok((_, mnemonic)) => {
+            println!("Create identity successful.");
+            Some(mnemonic)
+        }
+        Err(e) => {
+            println!("Create identity failed {:?}", e);
+            None
+        }
+    }
+}
+
+// the backup is saved in the documents folder
+async fn delete_account(
+    passphrase: String,
+    keep_backup: bool,
+) -> Result<DeletionReport, warp::error::Error> {
+    let backup_dir = if keep_backup {
+        Some(dirs::document_dir().unwrap_or_else(|| dirs::home_dir().unwrap_or_default()))
+    } else {
+        None
+    };
+    let (tx, rx) = oneshot::channel::<Result<DeletionReport, warp::error::Error>>();
+    WARP_CMD_CH
+        .tx
+        .send(WarpCmd::MultiPass(MultiPassCmd::DeleteAccount {
+            passphrase,
+            backup_dir,
+            rsp: tx,
+        }))
+        .expect("failed to send warp command");
+    rx.await.expect("failed to get response from warp_runner")
+}
+
+async fn recover_identity(mnemonic: String, new_passphrase: String) -> bool {
+    let (tx, rx) =
+        oneshot::channel::<Result<warp::multipass::identity::Identity, warp::error::Error>>();
+
+    let warp_cmd_tx = WARP_CMD_CH.tx.clone();
+    warp_cmd_tx
+        .send(WarpCmd::MultiPass(MultiPassCmd::RecoverIdentity {
+            mnemonic,
+            new_passphrase,
+            rsp: tx,
+        }))
+        .expect("failed to send warp command");
+
+    match rx.await.expect("failed to get response from warp_runner") {
+        Ok(_) => true,
+        Err(e) => {
+            log::error!("failed to recover identity: {e}");
             false
         }
     }
@@ -4,10 +4,10 @@ use derive_more::Display;
 
 use dioxus_desktop::{tao::window::WindowId, DesktopContext};
 use uuid::Uuid;
-use warp::crypto::DID;
+use warp::{crypto::DID, multipass::identity::IdentityStatus};
 use wry::webview::WebView;
 
-use crate::warp_runner::ui_adapter;
+use crate::warp_runner::{content_index::DuplicateAction, ui_adapter};
 
 use super::{
     chats::Chat,
@@ -53,6 +53,9 @@ pub enum Action<'a> {
     /// Sets the ID for the user.
     #[display(fmt = "SetId")]
     SetId(Identity),
+    /// Overrides the automatic status. None goes back to the automatic status
+    #[display(fmt = "SetManualStatus")]
+    SetManualStatus(Option<IdentityStatus>),
     /// adds an overlay. currently only used for demonstration purposes
     #[display(fmt = "AddOverlay")]
     AddOverlay(Weak<WebView>),
@@ -109,6 +112,15 @@ pub enum Action<'a> {
     Block(&'a DID),
     #[display(fmt = "Unblock")]
     Unblock(&'a DID),
+    /// local only. None removes the nickname
+    #[display(fmt = "SetNickname")]
+    SetNickname(&'a DID, Option<String>),
+    #[display(fmt = "SetContactNote")]
+    SetContactNote(&'a DID, String),
+    #[display(fmt = "AddContactTag")]
+    AddContactTag(&'a DID, String),
+    #[display(fmt = "RemoveContactTag")]
+    RemoveContactTag(&'a DID, String),
     /// Handles the display of "favorite" chats
     #[display(fmt = "Favorite")]
     Favorite(Uuid),
@@ -124,6 +136,12 @@ pub enum Action<'a> {
     /// Removes a chat from the sidebar, also removes the active chat if the chat being removed matches
     #[display(fmt = "RemoveFromSidebar")]
     RemoveFromSidebar(Uuid),
+    /// Moves a conversation started by someone who isn't a friend from the message requests to the sidebar
+    #[display(fmt = "AcceptMessageRequest")]
+    AcceptMessageRequest(Uuid),
+    /// Removes a conversation from the message requests. the conversation is deleted from warp separately
+    #[display(fmt = "RemoveMessageRequest")]
+    RemoveMessageRequest(Uuid),
     /// Adds or removes a chat from the favorites page
     #[display(fmt = "ToggleFavorite")]
     ToggleFavorite(&'a Uuid),
@@ -180,4 +198,14 @@ pub enum ConfigAction {
     SetSettingsNotificationsEnabled(bool),
     #[display(fmt = "SetAutoEnableExtensions {_0}")]
     SetAutoEnableExtensions(bool),
+    #[display(fmt = "SetLinkPreviewsEnabled {_0}")]
+    SetLinkPreviewsEnabled(bool),
+    #[display(fmt = "SetAwayTimeout {_0}")]
+    SetAwayTimeout(u64),
+    #[display(fmt = "SetAutoLockTimeout {_0}")]
+    SetAutoLockTimeout(u64),
+    #[display(fmt = "SetDuplicateUploadAction {_0:?}")]
+    SetDuplicateUploadAction(Option<DuplicateAction>),
+    #[display(fmt = "SetTrashRetention {_0}")]
+    SetTrashRetention(u64),
 }
@@ -0,0 +1,56 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+// locks the app after a period of inactivity. nothing here is saved: after a restart the user has to log in anyway
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AutoLock {
+    #[serde(skip)]
+    pub locked: bool,
+    // the last input reported by the UI, or when the window was focused
+    #[serde(skip)]
+    last_activity: Option<Instant>,
+    // notifications which arrived while locked. they are added to the UI once the user unlocks
+    #[serde(skip)]
+    pub held_messages: u32,
+    #[serde(skip)]
+    pub held_friend_requests: u32,
+}
+
+impl AutoLock {
+    pub fn record_activity(&mut self, now: Instant) {
+        self.last_activity = Some(now);
+    }
+
+    /// `timeout` is None if auto-lock is disabled
+    pub fn should_lock(&self, timeout: Option<Duration>, now: Instant) -> bool {
+        match (self.locked, self.last_activity, timeout) {
+            (false, Some(last_activity), Some(timeout)) => {
+                now.duration_since(last_activity) >= timeout
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TIMEOUT: Option<Duration> = Some(Duration::from_secs(600));
+
+    #[test]
+    fn locks_after_timeout() {
+        let start = Instant::now();
+        let mut auto_lock = AutoLock::default();
+        assert!(!auto_lock.should_lock(TIMEOUT, start + Duration::from_secs(900)));
+
+        auto_lock.record_activity(start);
+        assert!(!auto_lock.should_lock(TIMEOUT, start + Duration::from_secs(599)));
+        assert!(auto_lock.should_lock(TIMEOUT, start + Duration::from_secs(600)));
+        assert!(!auto_lock.should_lock(None, start + Duration::from_secs(600)));
+
+        auto_lock.locked = true;
+        assert!(!auto_lock.should_lock(TIMEOUT, start + Duration::from_secs(600)));
+    }
+}
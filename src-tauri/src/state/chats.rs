@@ -1,13 +1,20 @@
 use std::{
     collections::{HashMap, HashSet, VecDeque},
+    path::PathBuf,
     time::Instant,
 };
 
+use chrono::{DateTime, Utc};
 use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
 use uuid::Uuid;
 use warp::{crypto::DID, raygun};
 
-use crate::{warp_runner::ui_adapter, STATIC_ARGS};
+use crate::{
+    warp_runner::{archive, ui_adapter},
+    STATIC_ARGS,
+};
+
+use super::Identity;
 
 // warning: Chat implements Serialize
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
@@ -48,8 +55,43 @@ pub struct Chats {
     pub active_media: Option<Uuid>, // TODO: in the future, this should probably be a vec of media streams or something
     // Chats to show in the sidebar
     pub in_sidebar: VecDeque<Uuid>,
+    // Conversations started by someone who isn't a friend. They stay out of the sidebar until accepted.
+    #[serde(default)]
+    pub requests: VecDeque<Uuid>,
     // Favorite Chats
     pub favorites: Vec<Uuid>,
+    // Conversations imported from an archive. These are read-only and kept apart from `all` so that
+    // nothing tries to send to them.
+    #[serde(default)]
+    pub archived: HashMap<Uuid, ArchivedChat>,
+}
+
+// a conversation imported from an archive written by RayGunCmd::ExportConversation
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedChat {
+    pub chat: Chat,
+    // the participants may no longer be friends, so their usernames come from the archive
+    pub identities: Vec<Identity>,
+    pub exported_at: DateTime<Utc>,
+    pub imported_at: DateTime<Utc>,
+    // holds the archive and its attachments
+    pub directory: PathBuf,
+}
+
+impl ArchivedChat {
+    /// returns the location of the attachment, if it was included in the archive
+    pub fn attachment_path(&self, message_id: Uuid, file_name: &str) -> Option<PathBuf> {
+        let folder = self
+            .directory
+            .join(archive::ATTACHMENTS_DIR)
+            .join(message_id.to_string());
+        let path = folder.join(file_name);
+        // don't allow the file name to point outside of the archive
+        if path.parent()? != folder || !path.is_file() {
+            return None;
+        }
+        Some(path)
+    }
 }
 
 impl Chats {
@@ -79,7 +121,7 @@ impl Serialize for Chats {
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("Chats", 6)?;
+        let mut state = serializer.serialize_struct("Chats", 8)?;
 
         if STATIC_ARGS.use_mock {
             state.serialize_field("initialized", &self.initialized)?;
@@ -91,7 +133,9 @@ impl Serialize for Chats {
         state.serialize_field("active", &self.active)?;
         state.skip_field("active_media")?;
         state.serialize_field("in_sidebar", &self.in_sidebar)?;
+        state.serialize_field("requests", &self.requests)?;
         state.serialize_field("favorites", &self.favorites)?;
+        state.serialize_field("archived", &self.archived)?;
 
         state.end()
     }
@@ -1,7 +1,10 @@
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use warp::logging::tracing::log;
 
-use crate::STATIC_ARGS;
+use crate::{
+    warp_runner::{content_index::DuplicateAction, link_preview},
+    STATIC_ARGS,
+};
 
 use super::action::ConfigAction;
 
@@ -31,9 +34,13 @@ pub struct Configuration {
     /// Notification-related configuration options.
     #[serde(default)]
     pub notifications: Notifications,
+
+    /// File storage configuration options.
+    #[serde(default)]
+    pub files: Files,
 }
 
-#[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct General {
     #[serde(default)]
     pub theme: String,
@@ -41,6 +48,24 @@ pub struct General {
     pub show_splash: bool,
     #[serde(default)]
     pub enable_overlay: bool,
+    // the user is shown as Away after the window has been unfocused this long. 0 disables this
+    #[serde(default = "away_timeout_minutes_default")]
+    pub away_timeout_minutes: u64,
+}
+
+impl Default for General {
+    fn default() -> Self {
+        Self {
+            theme: String::new(),
+            show_splash: false,
+            enable_overlay: false,
+            away_timeout_minutes: away_timeout_minutes_default(),
+        }
+    }
+}
+
+fn away_timeout_minutes_default() -> u64 {
+    5
 }
 
 #[derive(Debug, Default, Deserialize, Serialize, Copy, Clone)]
@@ -49,6 +74,12 @@ pub struct Privacy {
     pub satellite_sync_nodes: bool,
     #[serde(default)]
     pub safer_file_scanning: bool,
+    /// fetching a preview reveals the user's IP address to the linked website, so this is opt-in
+    #[serde(default)]
+    pub link_previews: bool,
+    /// lock after this many minutes without activity. 0 disables auto-lock
+    #[serde(default)]
+    pub auto_lock_minutes: u64,
 }
 
 #[derive(Debug, Deserialize, Serialize, Copy, Clone, Eq, PartialEq)]
@@ -91,6 +122,29 @@ pub struct Developer {
     pub developer_mode: bool,
 }
 
+#[derive(Debug, Deserialize, Serialize, Copy, Clone)]
+pub struct Files {
+    /// what to do when an upload has the same contents as a file already in storage. None asks each time
+    #[serde(default)]
+    pub on_duplicate_upload: Option<DuplicateAction>,
+    /// deleted items are removed from the trash after this many days. 0 keeps them until the trash is emptied
+    #[serde(default = "trash_retention_days_default")]
+    pub trash_retention_days: u64,
+}
+
+impl Default for Files {
+    fn default() -> Self {
+        Self {
+            on_duplicate_upload: None,
+            trash_retention_days: trash_retention_days_default(),
+        }
+    }
+}
+
+fn trash_retention_days_default() -> u64 {
+    30
+}
+
 fn bool_true() -> bool {
     true
 }
@@ -167,20 +221,72 @@ impl Configuration {
             ConfigAction::SetAutoEnableExtensions(flag) => {
                 self.extensions.enable_automatically = flag
             }
+            ConfigAction::SetAwayTimeout(minutes) => self.general.away_timeout_minutes = minutes,
+            ConfigAction::SetAutoLockTimeout(minutes) => self.privacy.auto_lock_minutes = minutes,
+            ConfigAction::SetDuplicateUploadAction(action) => {
+                self.files.on_duplicate_upload = action
+            }
+            ConfigAction::SetTrashRetention(days) => self.files.trash_retention_days = days,
+            ConfigAction::SetLinkPreviewsEnabled(flag) => {
+                self.privacy.link_previews = flag;
+                link_preview::set_enabled(flag);
+            }
         }
 
         if self.audiovideo != old_audiovideo {
-            let contents = match serde_json::to_string(&self.audiovideo) {
-                Ok(c) => c,
+            let audiovideo = match serde_json::to_value(self.audiovideo) {
+                Ok(serde_json::Value::Object(map)) => map,
+                Ok(_) => return,
                 Err(e) => {
                     log::error!("failed to serialize audiovideo: {e}");
                     return;
                 }
             };
-            if let Err(e) = std::fs::write(&STATIC_ARGS.login_config_path, contents) {
-                log::error!("failed to save login_config: {e}");
-            }
+            // the audiovideo settings are stored at the top level of login_config.json, next to the keys
+            // written by `write_login_config`
+            let mut login_config = read_login_config_map();
+            login_config.extend(audiovideo);
+            save_login_config_map(login_config);
+        }
+    }
+}
+
+/// login_config.json can be read before the user logs in. returns None if `key` is missing or invalid
+pub fn read_login_config<T: DeserializeOwned>(key: &str) -> Option<T> {
+    let value = read_login_config_map().remove(key)?;
+    serde_json::from_value(value).ok()
+}
+
+pub fn write_login_config<T: Serialize>(key: &str, value: &T) {
+    let value = match serde_json::to_value(value) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("failed to serialize {key}: {e}");
+            return;
+        }
+    };
+    let mut login_config = read_login_config_map();
+    login_config.insert(key.to_string(), value);
+    save_login_config_map(login_config);
+}
+
+fn read_login_config_map() -> serde_json::Map<String, serde_json::Value> {
+    std::fs::read(&STATIC_ARGS.login_config_path)
+        .ok()
+        .and_then(|b| serde_json::from_slice(&b).ok())
+        .unwrap_or_default()
+}
+
+fn save_login_config_map(login_config: serde_json::Map<String, serde_json::Value>) {
+    let contents = match serde_json::to_string(&login_config) {
+        Ok(c) => c,
+        Err(e) => {
+            log::error!("failed to serialize login_config: {e}");
+            return;
         }
+    };
+    if let Err(e) = std::fs::write(&STATIC_ARGS.login_config_path, contents) {
+        log::error!("failed to save login_config: {e}");
     }
 }
 
@@ -0,0 +1,49 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+// information about a contact which is only stored locally and never sent to the contact
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ContactInfo {
+    // shown instead of the username chosen by the contact
+    #[serde(default)]
+    pub nickname: Option<String>,
+    #[serde(default)]
+    pub note: String,
+    // normalized by `normalize_tag`
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+}
+
+impl ContactInfo {
+    pub fn is_empty(&self) -> bool {
+        self.nickname.is_none() && self.note.is_empty() && self.tags.is_empty()
+    }
+}
+
+/// tags are lowercase and use dashes instead of whitespace, so "Team Infra" and "team-infra" are the same tag.
+/// returns None for an empty tag
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join("-")
+        .to_lowercase();
+    if tag.is_empty() {
+        None
+    } else {
+        Some(tag)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn normalize_tags() {
+        assert_eq!(normalize_tag(" Team  Infra "), Some("team-infra".into()));
+        assert_eq!(normalize_tag("team-infra"), Some("team-infra".into()));
+        assert_eq!(normalize_tag("   "), None);
+    }
+}
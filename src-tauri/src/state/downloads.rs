@@ -0,0 +1,80 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::warp_runner::downloads::{DownloadEvent, DownloadInfo, DownloadSource};
+
+// tracks the downloads reported by warp_runner::downloads. finished attachments are remembered per message
+// so they can be opened again without downloading them twice.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Downloads {
+    // queued and running downloads. these don't survive a restart
+    #[serde(skip_deserializing)]
+    pub active: HashMap<Uuid, ActiveDownload>,
+    // key: message id
+    #[serde(default)]
+    pub completed: HashMap<Uuid, Vec<CompletedDownload>>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ActiveDownload {
+    pub info: DownloadInfo,
+    pub current: usize,
+    pub total: Option<usize>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CompletedDownload {
+    pub file_name: String,
+    pub path: PathBuf,
+}
+
+impl Downloads {
+    pub fn process_event(&mut self, event: DownloadEvent) {
+        match event {
+            DownloadEvent::Queued(info) => {
+                self.active.insert(
+                    info.id,
+                    ActiveDownload {
+                        current: 0,
+                        total: info.total,
+                        info,
+                    },
+                );
+            }
+            DownloadEvent::Progress { id, current, total } => {
+                if let Some(download) = self.active.get_mut(&id) {
+                    download.current = current;
+                    download.total = total.or(download.total);
+                }
+            }
+            DownloadEvent::Finished { id } => {
+                let download = match self.active.remove(&id) {
+                    Some(d) => d,
+                    None => return,
+                };
+                if let DownloadSource::Attachment { message_id, .. } = download.info.source {
+                    let completed = self.completed.entry(message_id).or_default();
+                    completed.retain(|c| c.file_name != download.info.file_name);
+                    completed.push(CompletedDownload {
+                        file_name: download.info.file_name,
+                        path: download.info.destination,
+                    });
+                }
+            }
+            DownloadEvent::Failed { id } | DownloadEvent::Cancelled { id } => {
+                self.active.remove(&id);
+            }
+        }
+    }
+
+    /// returns where the attachment was saved, if it was downloaded and hasn't been moved or deleted since
+    pub fn get_completed(&self, message_id: Uuid, file_name: &str) -> Option<&PathBuf> {
+        self.completed
+            .get(&message_id)?
+            .iter()
+            .find(|c| c.file_name == file_name && c.path.exists())
+            .map(|c| &c.path)
+    }
+}
@@ -1,16 +1,20 @@
 pub mod action;
+pub mod auto_lock;
 pub mod chats;
 pub mod configuration;
+pub mod contacts;
+pub mod downloads;
 pub mod friends;
 pub mod identity;
 pub mod notifications;
+pub mod presence;
 pub mod route;
 // pub mod settings;
 pub mod storage;
 pub mod ui;
 
 // export specific structs which the UI expects. these structs used to be in src/state.rs, before state.rs was turned into the `state` folder
-use crate::warp_runner::ui_adapter;
+use crate::warp_runner::ui_adapter::{self, message_text};
 pub use action::Action;
 pub use chats::{Chat, Chats};
 use dioxus_desktop::tao::window::WindowId;
@@ -20,11 +24,14 @@ pub use route::Route;
 pub use ui::{Theme, ToastNotification, UI};
 use warp::multipass::identity::Platform;
 
-use crate::STATIC_ARGS;
+use crate::{language::get_local_text, STATIC_ARGS};
 
 use crate::{
     testing::mock::generate_mock,
     warp_runner::{
+        account_deletion::DeletionReport,
+        archive, link_preview,
+        safety_number::{self, SafetyNumber, VerifiedKey},
         ui_adapter::{MessageEvent, MultiPassEvent, RayGunEvent},
         WarpEvent,
     },
@@ -32,7 +39,7 @@ use crate::{
 use serde::{Deserialize, Serialize};
 use std::collections::{HashSet, VecDeque};
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, BTreeSet, HashMap},
     fmt, fs,
     time::{Duration, Instant},
 };
@@ -63,6 +70,31 @@ pub struct State {
     pub counter: i32,
     pub logged_in: bool,
     pub identities: HashMap<DID, identity::Identity>,
+    #[serde(default)]
+    pub downloads: downloads::Downloads,
+    // contacts whose safety number was checked by the user
+    #[serde(default)]
+    pub verified_contacts: HashMap<DID, VerifiedKey>,
+    // key: the DID which may replace a verified contact, value: the DID which was verified. the same DID if the
+    // safety number changed
+    #[serde(default)]
+    pub key_change_warnings: HashMap<DID, DID>,
+    // nicknames, notes, and tags. these are never shared
+    #[serde(default)]
+    pub contacts: HashMap<DID, contacts::ContactInfo>,
+    #[serde(default)]
+    pub presence: presence::Presence,
+    // shown once after the account is created. never saved or sent to the UI with the rest of the state
+    #[serde(skip)]
+    pub recovery_mnemonic: Option<String>,
+    #[serde(default)]
+    pub auto_lock: auto_lock::AutoLock,
+    // what was removed when the account was deleted. shown to the user but not loaded again
+    #[serde(skip_deserializing)]
+    pub deletion_report: Option<DeletionReport>,
+    // why the account couldn't be deleted. nothing was deleted and the user is still logged in
+    #[serde(skip_deserializing)]
+    pub deletion_error: Option<String>,
 }
 
 impl fmt::Debug for State {
@@ -93,6 +125,15 @@ impl Clone for State {
             counter: self.counter.clone(),
             logged_in: self.logged_in.clone(),
             identity_exists: self.identity_exists.clone(),
+            downloads: self.downloads.clone(),
+            verified_contacts: self.verified_contacts.clone(),
+            key_change_warnings: self.key_change_warnings.clone(),
+            contacts: self.contacts.clone(),
+            presence: self.presence.clone(),
+            recovery_mnemonic: self.recovery_mnemonic.clone(),
+            auto_lock: self.auto_lock.clone(),
+            deletion_report: self.deletion_report.clone(),
+            deletion_error: self.deletion_error.clone(),
         };
         state
     }
@@ -141,6 +182,22 @@ impl State {
             Action::RemoveFriend(friend) => self.remove_friend(friend),
             Action::Block(identity) => self.block(identity),
             Action::Unblock(identity) => self.unblock(identity),
+            Action::SetNickname(did, nickname) => self.set_nickname(did, nickname),
+            Action::SetContactNote(did, note) => self.update_contact(did, |c| c.note = note),
+            Action::AddContactTag(did, tag) => {
+                if let Some(tag) = contacts::normalize_tag(&tag) {
+                    self.update_contact(did, |c| {
+                        c.tags.insert(tag);
+                    });
+                }
+            }
+            Action::RemoveContactTag(did, tag) => {
+                if let Some(tag) = contacts::normalize_tag(&tag) {
+                    self.update_contact(did, |c| {
+                        c.tags.remove(&tag);
+                    });
+                }
+            }
 
             // ===== UI =====
             // Favorites
@@ -154,11 +211,19 @@ impl State {
             Action::SetOverlay(enabled) => self.toggle_overlay(enabled),
             // Sidebar
             Action::RemoveFromSidebar(chat_id) => self.remove_sidebar_chat(chat_id),
+            Action::AcceptMessageRequest(chat_id) => self.accept_message_request(chat_id),
+            Action::RemoveMessageRequest(chat_id) => self.remove_message_request(chat_id),
             Action::SidebarHidden(hidden) => self.ui.sidebar_hidden = hidden,
             // Navigation
             Action::Navigate(to) => self.set_active_route(to),
             // Generic UI
-            Action::SetMeta(metadata) => self.ui.metadata = metadata,
+            Action::SetMeta(metadata) => {
+                self.presence.set_focused(metadata.focused, Instant::now());
+                if metadata.focused {
+                    self.auto_lock.record_activity(Instant::now());
+                }
+                self.ui.metadata = metadata;
+            }
             Action::ClearPopout(window) => self.ui.clear_popout(window),
             Action::SetPopout(webview) => self.ui.set_popout(webview),
             // Development
@@ -193,11 +258,14 @@ impl State {
                 m.set_conversation_id(id);
                 m.set_sender(sender);
                 m.set_value(msg);
-                let m = ui_adapter::Message {
+                let mut m = ui_adapter::Message {
                     inner: m,
                     in_reply_to: None,
                     key: Uuid::new_v4().to_string(),
+                    text: Default::default(),
+                    link_previews: vec![],
                 };
+                self.parse_message_text(id, &mut m);
                 self.add_msg_to_chat(id, m);
             }
 
@@ -205,6 +273,7 @@ impl State {
             Action::ToggleMute => self.toggle_mute(),
             Action::ToggleSilence => self.toggle_silence(),
             Action::SetId(identity) => self.set_own_identity(identity),
+            Action::SetManualStatus(status) => self.presence.manual = status,
             Action::SetActiveMedia(id) => self.set_active_media(id),
             Action::DisableMedia => self.disable_media(),
 
@@ -221,11 +290,22 @@ impl State {
     }
 
     pub fn process_warp_event(&mut self, event: WarpEvent) {
+        // messages and identities aren't kept while locked. they are loaded again when the user logs in
+        if self.auto_lock.locked && !matches!(event, WarpEvent::Download(_)) {
+            self.hold_back_event(event);
+            return;
+        }
+        if let WarpEvent::Message(evt) = &event {
+            if self.is_from_blocked(evt) {
+                return;
+            }
+        }
         // handle any number of events and then save
         match event {
             WarpEvent::MultiPass(evt) => self.process_multipass_event(evt),
             WarpEvent::RayGun(evt) => self.process_raygun_event(evt),
             WarpEvent::Message(evt) => self.process_message_event(evt),
+            WarpEvent::Download(evt) => self.downloads.process_event(evt),
         };
 
         let _ = self.save();
@@ -247,12 +327,12 @@ impl State {
                 let notifications_enabled = self.configuration.notifications.friends_notifications;
 
                 if !self.ui.metadata.focused && notifications_enabled {
-                    // crate::notifications::push_notification(
-                    //     get_local_text("friends.new-request"),
-                    //     format!("{} sent a request.", identity.username()),
-                    //     Some(crate::sounds::Sounds::Notification),
-                    //     notify_rust::Timeout::Milliseconds(4),
-                    // );
+                    crate::notifications::push_notification(
+                        self.display_name(&identity),
+                        get_local_text("friends.user-sent-request"),
+                        None,
+                        notify_rust::Timeout::Milliseconds(4000),
+                    );
                 }
             }
             MultiPassEvent::FriendRequestSent(identity) => {
@@ -286,24 +366,45 @@ impl State {
             MultiPassEvent::IdentityUpdate(identity) => {
                 self.update_identity(identity.did_key(), identity);
             }
+            MultiPassEvent::VerifiedKeyChanged { previous, identity } => {
+                self.key_change_warnings
+                    .insert(identity.did_key(), previous);
+            }
         }
     }
 
     fn process_raygun_event(&mut self, event: RayGunEvent) {
         match event {
             RayGunEvent::ConversationCreated(chat) => {
-                if !self.chats.in_sidebar.contains(&chat.inner.id) {
-                    self.chats.in_sidebar.insert(0, chat.inner.id);
+                if chat
+                    .inner
+                    .participants
+                    .iter()
+                    .any(|did| self.friends.blocked.contains(did))
+                {
+                    return;
+                }
+                let id = chat.inner.id;
+                if !self.chats.in_sidebar.contains(&id) && !self.chats.requests.contains(&id) {
+                    // conversations started by strangers wait in the requests until the user accepts them
+                    if self.is_message_request(&chat.inner) {
+                        self.chats.requests.push_front(id);
+                    } else {
+                        self.chats.in_sidebar.push_front(id);
+                    }
                     self.identities.extend(
                         chat.identities
                             .iter()
                             .map(|ident| (ident.did_key(), ident.clone())),
                     );
                 }
-                self.chats.all.insert(chat.inner.id, chat.inner);
+                let conversation_id = chat.inner.id;
+                self.chats.all.insert(conversation_id, chat.inner);
+                self.parse_chat_messages(conversation_id);
             }
             RayGunEvent::ConversationDeleted(id) => {
                 self.chats.in_sidebar.retain(|x| *x != id);
+                self.chats.requests.retain(|x| *x != id);
                 self.chats.all.remove(&id);
                 if self.chats.active == Some(id) {
                     self.chats.active = None;
@@ -316,13 +417,21 @@ impl State {
         match event {
             MessageEvent::Received {
                 conversation_id,
-                message,
+                mut message,
             } => {
                 self.update_identity_status_hack(&message.inner.sender());
+                self.parse_message_text(conversation_id, &mut message);
+                let priority = message.text.priority;
                 let id = self.identities.get(&message.inner.sender()).cloned();
                 // todo: don't load all the messages by default. if the user scrolled up, for example, this incoming message may not need to be fetched yet.
                 self.add_msg_to_chat(conversation_id, message);
 
+                // message requests are shown without a notification or sound
+                if self.chats.requests.contains(&conversation_id) {
+                    self.chats.requests.retain(|id| *id != conversation_id);
+                    self.chats.requests.push_front(conversation_id);
+                    return;
+                }
                 if self.chats.in_sidebar.contains(&conversation_id) {
                     self.send_chat_to_top_of_sidebar(conversation_id);
                 }
@@ -337,8 +446,11 @@ impl State {
                 let notifications_enabled = self.configuration.notifications.messages_notifications;
                 let should_play_sound = self.chats.active != Some(conversation_id)
                     && self.configuration.audiovideo.message_sounds;
-                let should_dispatch_notification =
-                    notifications_enabled && !self.ui.metadata.focused;
+                // being mentioned overrides the message notification setting, but not the global one
+                let should_dispatch_notification = (notifications_enabled
+                    || (priority == notifications::NotificationPriority::High
+                        && self.configuration.notifications.enabled))
+                    && !self.ui.metadata.focused;
 
                 // This should be called if we have notifications enabled for new messages
                 if should_dispatch_notification {
@@ -347,16 +459,20 @@ impl State {
                     // } else {
                     //     None
                     // };
-                    // let text = match id {
-                    //     Some(id) => format!("{} {}", id.username(), None),
-                    //     None => println!(""),
-                    // };
-                    // crate::notifications::push_notification(
-                    //     // get_local_text("friends.new-request"),
-                    //     text,
-                    //     sound,
-                    //     notify_rust::Timeout::Milliseconds(4),
-                    // );
+                    let title = id
+                        .map(|id| self.display_name(&id))
+                        .unwrap_or_else(|| get_local_text("messages.new"));
+                    let content = if priority == notifications::NotificationPriority::High {
+                        get_local_text("messages.user-mentioned-you")
+                    } else {
+                        get_local_text("messages.user-sent-message")
+                    };
+                    crate::notifications::push_notification(
+                        title,
+                        content,
+                        None,
+                        notify_rust::Timeout::Milliseconds(4000),
+                    );
                     // If we don't have notifications enabled, but we still have sounds enabled, we should play the sound as long as we're not already actively focused on the convo where the message came from.
                 } else if should_play_sound {
                     // crate::sounds::Play(crate::sounds::Sounds::Notification);
@@ -364,19 +480,23 @@ impl State {
             }
             MessageEvent::Sent {
                 conversation_id,
-                message,
+                mut message,
             } => {
+                self.parse_message_text(conversation_id, &mut message);
                 // todo: don't load all the messages by default. if the user scrolled up, for example, this incoming message may not need to be fetched yet.
                 if let Some(chat) = self.chats.all.get_mut(&conversation_id) {
                     chat.messages.push_back(message);
                 }
+                // replying accepts the request. this also covers conversations the user started with a stranger
+                self.chats.requests.retain(|id| *id != conversation_id);
                 self.send_chat_to_top_of_sidebar(conversation_id);
             }
             MessageEvent::Edited {
                 conversation_id,
-                message,
+                mut message,
             } => {
                 self.update_identity_status_hack(&message.inner.sender());
+                self.parse_message_text(conversation_id, &mut message);
                 if let Some(chat) = self.chats.all.get_mut(&conversation_id) {
                     if let Some(msg) = chat
                         .messages
@@ -394,6 +514,7 @@ impl State {
                 if let Some(chat) = self.chats.all.get_mut(&conversation_id) {
                     chat.messages.retain(|msg| msg.inner.id() != message_id);
                 }
+                self.downloads.completed.remove(&message_id);
             }
             MessageEvent::MessageReactionAdded {
                 conversation_id,
@@ -429,6 +550,19 @@ impl State {
                     }
                 }
             }
+            MessageEvent::LinkPreviews {
+                conversation_id,
+                message_id,
+                previews,
+            } => {
+                if let Some(msg) = self.chats.all.get_mut(&conversation_id).and_then(|chat| {
+                    chat.messages
+                        .iter_mut()
+                        .find(|msg| msg.inner.id() == message_id)
+                }) {
+                    msg.link_previews = previews;
+                }
+            }
         }
     }
 }
@@ -496,6 +630,8 @@ impl State {
         state.logged_in = false;
         state.friends.initialized = false;
         state.chats.initialized = false;
+        link_preview::set_enabled(state.configuration.privacy.link_previews);
+        safety_number::set_verified_contacts(state.did_key(), state.verified_contacts.clone());
         state
     }
     fn load_mock() -> Self {
@@ -557,6 +693,7 @@ impl State {
             .collect()
     }
     pub fn set_chats(&mut self, chats: HashMap<Uuid, Chat>, identities: HashSet<Identity>) {
+        let ids: Vec<Uuid> = chats.keys().cloned().collect();
         for (id, chat) in chats {
             if let Some(conv) = self.chats.all.get_mut(&id) {
                 conv.messages = chat.messages;
@@ -567,6 +704,50 @@ impl State {
         self.chats.initialized = true;
         self.identities
             .extend(identities.iter().map(|x| (x.did_key(), x.clone())));
+        // needs the identities to resolve mentions
+        for id in ids {
+            self.parse_chat_messages(id);
+        }
+    }
+    /// Parses the message text into `ui_adapter::Message::text`, resolving mentions against the chat participants.
+    fn parse_message_text(&self, conversation_id: Uuid, message: &mut ui_adapter::Message) {
+        let mut participants = match self.chats.all.get(&conversation_id) {
+            Some(chat) => self.chat_participants(chat),
+            None => vec![],
+        };
+        participants.push(self.get_own_identity());
+        message.text =
+            message_text::parse_message(&message.inner.value(), &participants, &self.did_key());
+    }
+    fn parse_chat_messages(&mut self, conversation_id: Uuid) {
+        let mut messages = match self.chats.all.get_mut(&conversation_id) {
+            Some(chat) => std::mem::take(&mut chat.messages),
+            None => return,
+        };
+        for message in messages.iter_mut() {
+            self.parse_message_text(conversation_id, message);
+        }
+        if let Some(chat) = self.chats.all.get_mut(&conversation_id) {
+            chat.messages = messages;
+        }
+    }
+    /// Adds a conversation imported from an archive. Mentions are resolved against the usernames in the archive.
+    pub fn add_archived_chat(&mut self, mut archived: chats::ArchivedChat) {
+        let mut participants = archived.identities.clone();
+        participants.push(self.get_own_identity());
+        let own_did = self.did_key();
+        for message in archived.chat.messages.iter_mut() {
+            message.text =
+                message_text::parse_message(&message.inner.value(), &participants, &own_did);
+        }
+        self.chats.archived.insert(archived.chat.id, archived);
+    }
+    pub fn remove_archived_chat(&mut self, conversation_id: Uuid) {
+        if let Some(archived) = self.chats.archived.remove(&conversation_id) {
+            if let Err(e) = fs::remove_dir_all(&archived.directory) {
+                log::error!("failed to delete archive: {e}");
+            }
+        }
     }
     fn add_msg_to_chat(&mut self, conversation_id: Uuid, message: ui_adapter::Message) {
         if let Some(chat) = self.chats.all.get_mut(&conversation_id) {
@@ -755,6 +936,39 @@ impl State {
         }
     }
 
+    // true if none of the other participants is a friend
+    fn is_message_request(&self, chat: &Chat) -> bool {
+        let own_did = self.did_key();
+        !chat
+            .participants
+            .iter()
+            .filter(|did| **did != own_did)
+            .any(|did| self.friends.all.contains(did))
+    }
+    fn is_from_blocked(&self, event: &MessageEvent) -> bool {
+        let sender = match event {
+            MessageEvent::Received { message, .. } | MessageEvent::Edited { message, .. } => {
+                message.inner.sender()
+            }
+            MessageEvent::TypingIndicator { participant, .. } => participant.clone(),
+            _ => return false,
+        };
+        self.friends.blocked.contains(&sender)
+    }
+    fn accept_message_request(&mut self, chat_id: Uuid) {
+        if !self.chats.requests.contains(&chat_id) {
+            return;
+        }
+        self.chats.requests.retain(|id| *id != chat_id);
+        self.send_chat_to_top_of_sidebar(chat_id);
+    }
+    fn remove_message_request(&mut self, chat_id: Uuid) {
+        self.chats.requests.retain(|id| *id != chat_id);
+        self.chats.all.remove(&chat_id);
+        if self.chats.active == Some(chat_id) {
+            self.clear_active_chat();
+        }
+    }
     fn send_chat_to_top_of_sidebar(&mut self, chat_id: Uuid) {
         self.chats.in_sidebar.retain(|id| id != &chat_id);
         self.chats.in_sidebar.push_front(chat_id);
@@ -828,7 +1042,9 @@ impl State {
         self.friends.outgoing_requests.insert(identity.did_key());
         self.identities.insert(identity.did_key(), identity.clone());
     }
+    /// friends are grouped and sorted by their nickname, if they have one, and otherwise their username
     pub fn get_friends_by_first_letter(
+        &self,
         friends: HashMap<DID, Identity>,
     ) -> BTreeMap<char, Vec<Identity>> {
         let mut friends_by_first_letter: BTreeMap<char, Vec<Identity>> = BTreeMap::new();
@@ -836,8 +1052,8 @@ impl State {
         // Iterate over the friends and add each one to the appropriate Vec in the
         // friends_by_first_letter HashMap
         for (_, friend) in friends {
-            let first_letter = friend
-                .username()
+            let first_letter = self
+                .display_name(&friend)
                 .chars()
                 .next()
                 .expect("all friends should have a username")
@@ -850,11 +1066,61 @@ impl State {
         }
 
         for (_, list) in friends_by_first_letter.iter_mut() {
-            list.sort_by_key(|a| a.username())
+            list.sort_by_key(|a| self.display_name(a))
         }
 
         friends_by_first_letter
     }
+    /// returns the friends which have the given tag
+    pub fn get_friends_by_tag(&self, tag: &str) -> Vec<Identity> {
+        let tag = match contacts::normalize_tag(tag) {
+            Some(t) => t,
+            None => return vec![],
+        };
+        let mut friends: Vec<Identity> = self
+            .friends
+            .all
+            .iter()
+            .filter(|did| {
+                self.contacts
+                    .get(did)
+                    .map(|c| c.tags.contains(&tag))
+                    .unwrap_or(false)
+            })
+            .filter_map(|did| self.identities.get(did))
+            .cloned()
+            .collect();
+        friends.sort_by_key(|a| self.display_name(a));
+        friends
+    }
+    /// every tag which is used by at least one contact
+    pub fn contact_tags(&self) -> BTreeSet<String> {
+        self.contacts
+            .values()
+            .flat_map(|c| c.tags.iter().cloned())
+            .collect()
+    }
+    /// the nickname given to the identity, or its username if it doesn't have one
+    pub fn display_name(&self, identity: &Identity) -> String {
+        self.contacts
+            .get(&identity.did_key())
+            .and_then(|c| c.nickname.clone())
+            .unwrap_or_else(|| identity.username())
+    }
+    fn set_nickname(&mut self, did: &DID, nickname: Option<String>) {
+        let nickname = nickname
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty());
+        self.update_contact(did, |c| c.nickname = nickname);
+    }
+    // removes the entry if nothing is left in it
+    fn update_contact(&mut self, did: &DID, f: impl FnOnce(&mut contacts::ContactInfo)) {
+        let contact = self.contacts.entry(did.clone()).or_default();
+        f(contact);
+        if contact.is_empty() {
+            self.contacts.remove(did);
+        }
+    }
     pub fn has_friend_with_did(&self, did: &DID) -> bool {
         self.friends.all.contains(did)
     }
@@ -926,6 +1192,108 @@ impl State {
             false
         }
     }
+    pub fn set_window_focused(&mut self, focused: bool) {
+        self.presence.set_focused(focused, Instant::now());
+        if focused {
+            self.auto_lock.record_activity(Instant::now());
+        }
+        self.ui.metadata.focused = focused;
+    }
+    /// recomputes the user's status. returns the new status if it changed and needs to be sent to MultiPass
+    pub fn update_presence(&mut self) -> Option<IdentityStatus> {
+        let away_after = match self.configuration.general.away_timeout_minutes {
+            0 => None,
+            minutes => Some(Duration::from_secs(minutes * 60)),
+        };
+        let status = self.presence.status(
+            self.chats.active_media.is_some(),
+            away_after,
+            Instant::now(),
+        );
+        if self.presence.published == Some(status) {
+            return None;
+        }
+        self.presence.published = Some(status);
+        let own_did = self.did_key();
+        if let Some(ident) = self.identities.get_mut(&own_did) {
+            ident.set_identity_status(status);
+        }
+        Some(status)
+    }
+    pub fn record_activity(&mut self) {
+        self.auto_lock.record_activity(Instant::now());
+    }
+    /// returns true if the inactivity timeout has passed. the caller then locks tesseract
+    pub fn check_auto_lock(&mut self) -> bool {
+        let timeout = match self.configuration.privacy.auto_lock_minutes {
+            0 => None,
+            minutes => Some(Duration::from_secs(minutes * 60)),
+        };
+        if !self.logged_in || !self.auto_lock.should_lock(timeout, Instant::now()) {
+            return false;
+        }
+        self.lock();
+        true
+    }
+    /// clears messages and identities from memory. the UI shows the unlock view because logged_in is false
+    pub fn lock(&mut self) {
+        self.auto_lock.locked = true;
+        self.logged_in = false;
+        for chat in self.chats.all.values_mut() {
+            chat.messages.clear();
+            chat.replying_to = None;
+        }
+        // read from disk again when unlocked
+        for archived in self.chats.archived.values_mut() {
+            archived.chat.messages.clear();
+        }
+        self.identities.clear();
+        ui_adapter::identity_cache::clear();
+        self.ui.toast_notifications.clear();
+    }
+    /// called after the user logged in again. adds the notifications which were held back while locked
+    pub fn unlock(&mut self) {
+        if !self.auto_lock.locked {
+            return;
+        }
+        self.auto_lock.locked = false;
+        self.auto_lock.record_activity(Instant::now());
+        for (_, mut archived) in std::mem::take(&mut self.chats.archived) {
+            match archive::read_imported(&archived.directory) {
+                Ok(contents) => {
+                    archived.chat.messages =
+                        ui_adapter::archive_to_chat(&contents, archived.directory.clone())
+                            .chat
+                            .messages;
+                }
+                Err(e) => log::error!("failed to read archive: {e}"),
+            }
+            self.add_archived_chat(archived);
+        }
+        let messages = std::mem::take(&mut self.auto_lock.held_messages);
+        if messages > 0 {
+            self.mutate(Action::AddNotification(
+                notifications::NotificationKind::Message,
+                messages,
+            ));
+        }
+        let friend_requests = std::mem::take(&mut self.auto_lock.held_friend_requests);
+        if friend_requests > 0 {
+            self.mutate(Action::AddNotification(
+                notifications::NotificationKind::FriendRequest,
+                friend_requests,
+            ));
+        }
+    }
+    fn hold_back_event(&mut self, event: WarpEvent) {
+        match event {
+            WarpEvent::Message(MessageEvent::Received { .. }) => self.auto_lock.held_messages += 1,
+            WarpEvent::MultiPass(MultiPassEvent::FriendRequestReceived(_)) => {
+                self.auto_lock.held_friend_requests += 1
+            }
+            _ => {}
+        }
+    }
     /// Analogous to Hang Up
     fn disable_media(&mut self) {
         self.chats.active_media = None;
@@ -1045,6 +1413,31 @@ impl State {
     pub fn set_own_identity(&mut self, identity: Identity) {
         self.id = identity.did_key();
         self.identities.insert(identity.did_key(), identity);
+        // verifications are tied to the account's key
+        safety_number::set_verified_contacts(self.did_key(), self.verified_contacts.clone());
+    }
+    pub fn safety_number(&self, did: &DID) -> SafetyNumber {
+        safety_number::safety_number(&self.did_key(), did)
+    }
+    /// true if the user verified this contact's safety number and the key hasn't changed since
+    pub fn is_verified(&self, did: &DID) -> bool {
+        self.verified_contacts
+            .get(did)
+            .map(|verified| verified.matches(&self.did_key(), did))
+            .unwrap_or(false)
+    }
+    pub fn set_verified(&mut self, did: DID, verified: bool) {
+        if verified {
+            let username = self.get_identity(&did).username();
+            self.key_change_warnings.remove(&did);
+            self.verified_contacts.insert(
+                did.clone(),
+                VerifiedKey::new(&self.did_key(), &did, username),
+            );
+        } else {
+            self.verified_contacts.remove(&did);
+        }
+        safety_number::set_verified_contacts(self.did_key(), self.verified_contacts.clone());
     }
     pub fn update_identity(&mut self, id: DID, ident: identity::Identity) {
         if let Some(friend) = self.identities.get_mut(&id) {
@@ -1070,10 +1463,10 @@ impl State {
             .map(|x| x.graphics())
             .unwrap_or_default()
     }
-    pub fn join_usernames(identities: &[Identity]) -> String {
+    pub fn join_usernames(&self, identities: &[Identity]) -> String {
         identities
             .iter()
-            .map(|x| x.username())
+            .map(|x| self.display_name(x))
             .collect::<Vec<String>>()
             .join(", ")
     }
@@ -11,6 +11,19 @@ pub enum NotificationKind {
     Settings,
 }
 
+// Messages which mention the current user are High priority and may notify even when message notifications are disabled.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum NotificationPriority {
+    Normal,
+    High,
+}
+
+impl Default for NotificationPriority {
+    fn default() -> Self {
+        Self::Normal
+    }
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Notifications {
     pub friends: u32, // For notifications about new friends, friend requests and related CTAs.
@@ -0,0 +1,107 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use warp::multipass::identity::IdentityStatus;
+
+// decides which status is published for the user. State::update_presence sends changes to MultiPass
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Presence {
+    // picked by the user. overrides the automatic status until it is cleared
+    #[serde(default)]
+    pub manual: Option<IdentityStatus>,
+    // when the window lost focus. None while the window is focused
+    #[serde(skip)]
+    pub unfocused_since: Option<Instant>,
+    // the status which was last sent to MultiPass
+    #[serde(skip)]
+    pub published: Option<IdentityStatus>,
+}
+
+impl Presence {
+    pub fn set_focused(&mut self, focused: bool, now: Instant) {
+        if focused {
+            self.unfocused_since = None;
+        } else if self.unfocused_since.is_none() {
+            self.unfocused_since = Some(now);
+        }
+    }
+
+    /// the manual status wins, followed by Busy during a call, and Away once the window has been unfocused
+    /// for longer than `away_after`. if `away_after` is None the user never becomes Away automatically.
+    pub fn status(
+        &self,
+        in_call: bool,
+        away_after: Option<Duration>,
+        now: Instant,
+    ) -> IdentityStatus {
+        if let Some(status) = self.manual {
+            return status;
+        }
+        if in_call {
+            return IdentityStatus::Busy;
+        }
+        let idle = match (self.unfocused_since, away_after) {
+            (Some(since), Some(away_after)) => now.duration_since(since) >= away_after,
+            _ => false,
+        };
+        if idle {
+            IdentityStatus::Away
+        } else {
+            IdentityStatus::Online
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const AWAY_AFTER: Option<Duration> = Some(Duration::from_secs(60));
+
+    #[test]
+    fn away_after_idle_timeout() {
+        let start = Instant::now();
+        let mut presence = Presence::default();
+        presence.set_focused(false, start);
+
+        let soon = start + Duration::from_secs(30);
+        assert_eq!(
+            presence.status(false, AWAY_AFTER, soon),
+            IdentityStatus::Online
+        );
+        let later = start + Duration::from_secs(61);
+        assert_eq!(
+            presence.status(false, AWAY_AFTER, later),
+            IdentityStatus::Away
+        );
+        assert_eq!(presence.status(false, None, later), IdentityStatus::Online);
+
+        presence.set_focused(true, later);
+        assert_eq!(
+            presence.status(false, AWAY_AFTER, later),
+            IdentityStatus::Online
+        );
+    }
+
+    #[test]
+    fn busy_during_call_even_when_idle() {
+        let start = Instant::now();
+        let mut presence = Presence::default();
+        presence.set_focused(false, start);
+        let later = start + Duration::from_secs(120);
+        assert_eq!(
+            presence.status(true, AWAY_AFTER, later),
+            IdentityStatus::Busy
+        );
+    }
+
+    #[test]
+    fn manual_status_overrides() {
+        let now = Instant::now();
+        let presence = Presence {
+            manual: Some(IdentityStatus::Away),
+            ..Default::default()
+        };
+        assert_eq!(presence.status(true, AWAY_AFTER, now), IdentityStatus::Away);
+    }
+}
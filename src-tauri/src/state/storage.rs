@@ -1,6 +1,9 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use warp::{constellation::directory::Directory, constellation::file::File};
 
+use crate::warp_runner::{DOCUMENT_EXTENSIONS, IMAGE_EXTENSIONS, VIDEO_FILE_EXTENSIONS};
+
 // TODO: Properly wrap data which is expected to persist remotely in options, so we can know if we're still figuring out what exists "remotely", i.e. loading.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Storage {
@@ -10,9 +13,15 @@ pub struct Storage {
     // Info about current directory opened
     #[serde(skip)]
     pub current_dir: Directory,
+    // Path of the current directory, e.g. "/photos/2020"
+    #[serde(skip)]
+    pub path: String,
     // All directories opened until current directory, inclusive current directory
     #[serde(skip)]
     pub directories_opened: Vec<Directory>,
+    // One for each of `directories_opened`, so the UI can link to them
+    #[serde(skip)]
+    pub breadcrumbs: Vec<Breadcrumb>,
     // List of directories inside current directory
     #[serde(skip)]
     pub directories: Vec<Directory>,
@@ -20,3 +29,164 @@ pub struct Storage {
     #[serde(skip)]
     pub files: Vec<File>,
 }
+
+// what the UI is sent about a Storage. warp's Directory and File aren't sent as they are
+#[derive(Clone, Debug, Serialize)]
+pub struct DirectoryListing {
+    pub path: String,
+    pub breadcrumbs: Vec<Breadcrumb>,
+    pub directories: Vec<ListedItem>,
+    pub files: Vec<ListedItem>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ListedItem {
+    pub name: String,
+    pub path: String,
+    pub size: usize,
+    pub created: DateTime<Utc>,
+    pub modified: DateTime<Utc>,
+}
+
+impl From<&Storage> for DirectoryListing {
+    fn from(storage: &Storage) -> Self {
+        let path = |name: &str| format!("{}/{name}", storage.path.trim_end_matches('/'));
+        Self {
+            path: storage.path.clone(),
+            breadcrumbs: storage.breadcrumbs.clone(),
+            directories: storage
+                .directories
+                .iter()
+                .map(|dir| ListedItem {
+                    path: path(&dir.name()),
+                    name: dir.name(),
+                    size: dir.size(),
+                    created: dir.creation(),
+                    modified: dir.modified(),
+                })
+                .collect(),
+            files: storage
+                .files
+                .iter()
+                .map(|file| ListedItem {
+                    path: path(&file.name()),
+                    name: file.name(),
+                    size: file.size(),
+                    created: file.creation(),
+                    modified: file.modified(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Breadcrumb {
+    pub name: String,
+    pub path: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ItemStat {
+    pub name: String,
+    pub path: String,
+    pub is_directory: bool,
+    pub size: usize,
+    pub created: DateTime<Utc>,
+    pub modified: DateTime<Utc>,
+    // from the root directory to the item, or to the directory containing it if it's a file
+    pub breadcrumbs: Vec<Breadcrumb>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StorageStats {
+    // in bytes, including the trash
+    pub total_size: usize,
+    pub readable_total: String,
+    // the files in the trash aren't counted here, or in the fields below
+    pub file_count: usize,
+    // the size of each folder in the root directory, largest first. files in the root directory aren't in any folder
+    pub folders: Vec<(String, usize)>,
+    pub by_type: TypeBreakdown,
+    // (path, size), largest first
+    pub largest_files: Vec<(String, usize)>,
+    // what the items in the trash take up
+    pub trash_size: usize,
+    // None if Constellation doesn't limit how much can be stored
+    pub quota: Option<usize>,
+    pub remaining: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileCategory {
+    Image,
+    Video,
+    Document,
+    Other,
+}
+
+impl FileCategory {
+    pub fn from_name(name: &str) -> Self {
+        let extension = match name.rsplit_once('.') {
+            Some((_, ext)) => format!(".{}", ext.to_lowercase()),
+            None => return FileCategory::Other,
+        };
+        let is = |extensions: &[&str]| extensions.contains(&extension.as_str());
+        if is(IMAGE_EXTENSIONS) {
+            FileCategory::Image
+        } else if is(VIDEO_FILE_EXTENSIONS) {
+            FileCategory::Video
+        } else if is(DOCUMENT_EXTENSIONS) {
+            FileCategory::Document
+        } else {
+            FileCategory::Other
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FileSearchResult {
+    pub name: String,
+    // e.g. "/photos/2020/cat.png"
+    pub path: String,
+    // the directory containing the file, e.g. "/photos/2020"
+    pub directory: String,
+    pub size: usize,
+    pub modified: DateTime<Utc>,
+    pub kind: FileCategory,
+}
+
+// bytes used by each FileCategory
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TypeBreakdown {
+    pub image: usize,
+    pub video: usize,
+    pub document: usize,
+    pub other: usize,
+}
+
+impl TypeBreakdown {
+    pub fn add(&mut self, category: FileCategory, size: usize) {
+        match category {
+            FileCategory::Image => self.image += size,
+            FileCategory::Video => self.video += size,
+            FileCategory::Document => self.document += size,
+            FileCategory::Other => self.other += size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn files_are_categorized_by_extension() {
+        assert_eq!(FileCategory::from_name("cat.PNG"), FileCategory::Image);
+        assert_eq!(FileCategory::from_name("clip.mp4"), FileCategory::Video);
+        assert_eq!(FileCategory::from_name("notes.pdf"), FileCategory::Document);
+        assert_eq!(FileCategory::from_name("archive.zip"), FileCategory::Other);
+        assert_eq!(FileCategory::from_name("README"), FileCategory::Other);
+    }
+}
@@ -19,7 +19,10 @@ use warp::{
     raygun::Message,
 };
 
-use crate::state::{storage::Storage, Chat, Chats, Friends, Identity, State, ToastNotification};
+use crate::state::{
+    storage::{Breadcrumb, Storage},
+    Chat, Chats, Friends, Identity, State, ToastNotification,
+};
 
 use crate::warp_runner::ui_adapter;
 
@@ -112,6 +115,8 @@ fn generate_fake_chat(participants: Vec<Identity>, conversation: Uuid) -> Chat {
             inner: default_message,
             in_reply_to: None,
             key: Uuid::new_v4().to_string(),
+            text: Default::default(),
+            link_previews: vec![],
         });
     }
 
@@ -256,6 +261,8 @@ fn generate_fake_message(conversation_id: Uuid, identities: &[Identity]) -> ui_a
         inner: default_message,
         in_reply_to: None,
         key: Uuid::new_v4().to_string(),
+        text: Default::default(),
+        link_previews: vec![],
     }
 }
 
@@ -275,7 +282,12 @@ fn generate_fake_storage() -> Storage {
     Storage {
         initialized: true,
         current_dir: Directory::new("root"),
+        path: "/".into(),
         directories_opened: vec![Directory::new("root")],
+        breadcrumbs: vec![Breadcrumb {
+            name: "root".into(),
+            path: "/".into(),
+        }],
         directories,
         files,
     }
@@ -0,0 +1,174 @@
+//! deletes everything Uplink stores for an account: the tesseract keystore, warp's blocks, state.json, and logs.
+//!
+//! files are overwritten with zeros before they are removed. on SSDs and copy-on-write file systems the old blocks
+//! may survive anyway, so this reduces what can be recovered but can't guarantee that nothing can be.
+//!
+//! warp must be closed first, because it keeps files in the warp directory open. manager::run makes the backup while
+//! warp is still running, so that nothing is closed if it fails, and then closes warp before calling `delete_account`.
+
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+use warp::{crypto::cipher::Cipher, error::Error, logging::tracing::log, tesseract::Tesseract};
+
+use super::{login_throttle, manager::commands::compress_folder, ui_adapter::identity_cache};
+use crate::STATIC_ARGS;
+
+const OVERWRITE_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeletionReport {
+    // files which were overwritten and removed, with their size in bytes
+    pub deleted: Vec<(PathBuf, u64)>,
+    pub removed_dirs: Vec<PathBuf>,
+    // files or folders which couldn't be deleted, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+    // the encrypted copy of the account which was made before deleting it
+    pub backup: Option<PathBuf>,
+}
+
+/// checks the passphrase against the keystore on disk. failures count towards the login backoff
+pub fn verify_passphrase(passphrase: &str) -> Result<(), Error> {
+    let mut file = fs::File::open(&STATIC_ARGS.tesseract_path)?;
+    let tesseract = Tesseract::from_reader(&mut file)?;
+    login_throttle::unlock(&tesseract, passphrase)?;
+    tesseract.lock();
+    Ok(())
+}
+
+/// `backup`: the file made by `backup_account`, if any. files which can't be deleted are listed in the report
+pub fn delete_account(backup: Option<PathBuf>) -> DeletionReport {
+    let mut report = DeletionReport {
+        backup,
+        ..Default::default()
+    };
+
+    identity_cache::clear();
+    let root = STATIC_ARGS.uplink_path.clone();
+    // the keystore goes first. without it the rest of the data can't be tied to the account
+    wipe_file(&STATIC_ARGS.tesseract_path, &mut report);
+    wipe_dir(&root, &mut report);
+
+    // warp_runner starts over after this and expects the warp directory to exist
+    if let Err(e) = fs::create_dir_all(&STATIC_ARGS.warp_path) {
+        log::error!("failed to create warp directory: {e}");
+    }
+    log::info!(
+        "deleted {} files, {} failed",
+        report.deleted.len(),
+        report.failed.len()
+    );
+    report
+}
+
+/// saves an encrypted zip of the account in `dir`. it can only be decrypted with `passphrase`
+pub async fn backup_account(passphrase: &str, dir: &Path) -> Result<PathBuf, Error> {
+    let temp_dir = tempfile::TempDir::new()?;
+    let zip_path = temp_dir.path().join("backup.zip");
+    compress_folder(STATIC_ARGS.uplink_path.clone(), zip_path.clone()).await?;
+
+    let contents = tokio::fs::read(&zip_path).await?;
+    let encrypted = Cipher::direct_encrypt(&contents, passphrase.as_bytes())?;
+    let name = format!(
+        "light-backup-{}.zip.enc",
+        Utc::now().format("%Y%m%d-%H%M%S")
+    );
+    let path = dir.join(name);
+    tokio::fs::write(&path, encrypted).await?;
+    Ok(path)
+}
+
+fn wipe_dir(dir: &Path, report: &mut DeletionReport) {
+    if !dir.exists() {
+        return;
+    }
+    // files first, then the folders which contained them, deepest first
+    let mut dirs = vec![];
+    for entry in WalkDir::new(dir).contents_first(true) {
+        match entry {
+            Ok(entry) if entry.file_type().is_dir() => dirs.push(entry.into_path()),
+            Ok(entry) => wipe_file(entry.path(), report),
+            Err(e) => report.failed.push((
+                e.path().map(Path::to_path_buf).unwrap_or_default(),
+                e.to_string(),
+            )),
+        }
+    }
+    for dir in dirs {
+        match fs::remove_dir(&dir) {
+            Ok(_) => report.removed_dirs.push(dir),
+            Err(e) => report.failed.push((dir, e.to_string())),
+        }
+    }
+}
+
+fn wipe_file(path: &Path, report: &mut DeletionReport) {
+    if !path.exists() {
+        return;
+    }
+    match overwrite(path).and_then(|size| fs::remove_file(path).map(|_| size)) {
+        Ok(size) => report.deleted.push((path.to_path_buf(), size)),
+        Err(e) => report.failed.push((path.to_path_buf(), e.to_string())),
+    }
+}
+
+// returns the size of the file
+fn overwrite(path: &Path) -> io::Result<u64> {
+    let meta = fs::symlink_metadata(path)?;
+    // only the link itself is removed, not what it points to
+    if meta.file_type().is_symlink() {
+        return Ok(0);
+    }
+    let size = meta.len();
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+    let zeros = vec![0u8; OVERWRITE_CHUNK_SIZE];
+    let mut remaining = size;
+    while remaining > 0 {
+        let n = remaining.min(OVERWRITE_CHUNK_SIZE as u64) as usize;
+        file.write_all(&zeros[..n])?;
+        remaining -= n as u64;
+    }
+    file.sync_all()?;
+    Ok(size)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wipe_dir_removes_everything() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("light");
+        fs::create_dir_all(dir.join("warp/blocks")).unwrap();
+        fs::write(dir.join("state.json"), b"{}").unwrap();
+        fs::write(dir.join("warp/blocks/a"), vec![1u8; 100_000]).unwrap();
+
+        let mut report = DeletionReport::default();
+        wipe_dir(&dir, &mut report);
+
+        assert!(!dir.exists());
+        assert!(report.failed.is_empty());
+        assert_eq!(report.deleted.len(), 2);
+        assert!(report
+            .deleted
+            .contains(&(dir.join("warp/blocks/a"), 100_000)));
+        assert_eq!(report.removed_dirs.len(), 3);
+    }
+
+    #[test]
+    fn overwrite_keeps_size_and_clears_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a");
+        fs::write(&path, b"secret").unwrap();
+
+        assert_eq!(overwrite(&path).unwrap(), 6);
+        assert_eq!(fs::read(&path).unwrap(), vec![0u8; 6]);
+    }
+}
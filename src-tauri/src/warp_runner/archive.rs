@@ -0,0 +1,304 @@
+//! the format used to export conversations. the JSON rendering is the canonical one and is what gets imported again,
+//! Markdown and HTML are for reading.
+
+use std::{fs, ops::Range, path::Path};
+
+use chrono::{DateTime, Utc};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use warp::error::Error;
+
+use crate::warp_runner::ui_adapter::message_text::escape_html;
+
+// bump this when a change to the structs below would break importing older archives
+pub const ARCHIVE_VERSION: u32 = 1;
+
+// attachments are bundled in this folder, next to the export file, when the export is zipped
+pub const ATTACHMENTS_DIR: &str = "attachments";
+
+#[derive(Display, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    #[display(fmt = "json")]
+    Json,
+    #[display(fmt = "md")]
+    Markdown,
+    #[display(fmt = "html")]
+    Html,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> String {
+        self.to_string()
+    }
+}
+
+// messages sent within this range are exported. None exports the whole conversation
+pub type ExportRange = Option<Range<DateTime<Utc>>>;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConversationArchive {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub conversation_id: Uuid,
+    pub participants: Vec<ArchivedParticipant>,
+    // oldest first
+    pub messages: Vec<ArchivedMessage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedParticipant {
+    // DIDs are stored as strings so that an archive can be read without warp
+    pub did: String,
+    pub username: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedMessage {
+    pub id: Uuid,
+    pub sender: String,
+    pub sender_name: String,
+    pub date: DateTime<Utc>,
+    #[serde(default)]
+    pub in_reply_to: Option<Uuid>,
+    pub lines: Vec<String>,
+    #[serde(default)]
+    pub reactions: Vec<ArchivedReaction>,
+    #[serde(default)]
+    pub attachments: Vec<ArchivedAttachment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedReaction {
+    pub emoji: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedAttachment {
+    pub name: String,
+    pub size: usize,
+    // relative to the export file. only set if the attachment was bundled
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+impl ConversationArchive {
+    pub fn render(&self, format: ExportFormat) -> Result<String, serde_json::Error> {
+        match format {
+            ExportFormat::Json => serde_json::to_string_pretty(self),
+            ExportFormat::Markdown => Ok(self.to_markdown()),
+            ExportFormat::Html => Ok(self.to_html()),
+        }
+    }
+
+    fn title(&self) -> String {
+        let names: Vec<&str> = self
+            .participants
+            .iter()
+            .map(|p| p.username.as_str())
+            .collect();
+        format!("Conversation with {}", names.join(", "))
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut md = format!(
+            "# {}\n\nExported {}\n\n",
+            self.title(),
+            format_date(&self.exported_at)
+        );
+        for msg in &self.messages {
+            md.push_str(&format!(
+                "**{}** ({})\n\n",
+                msg.sender_name,
+                format_date(&msg.date)
+            ));
+            if let Some(reply) = msg.in_reply_to {
+                md.push_str(&format!("> in reply to {reply}\n\n"));
+            }
+            for line in &msg.lines {
+                md.push_str(line);
+                md.push('\n');
+            }
+            md.push('\n');
+            for attachment in &msg.attachments {
+                match &attachment.path {
+                    Some(path) => md.push_str(&format!(
+                        "- Attachment: [{}]({})\n",
+                        attachment.name,
+                        path.replace(' ', "%20")
+                    )),
+                    None => md.push_str(&format!("- Attachment: {}\n", attachment.name)),
+                }
+            }
+            if !msg.reactions.is_empty() {
+                let reactions: Vec<String> = msg
+                    .reactions
+                    .iter()
+                    .map(|r| format!("{} {}", r.emoji, r.count))
+                    .collect();
+                md.push_str(&format!("- Reactions: {}\n", reactions.join(", ")));
+            }
+            md.push_str("\n---\n\n");
+        }
+        md
+    }
+
+    // message text is shown as it was typed, not rendered as markdown, so the archive shows exactly what was sent
+    fn to_html(&self) -> String {
+        let mut body = String::new();
+        for msg in &self.messages {
+            body.push_str("<div class=\"message\">\n");
+            body.push_str(&format!(
+                "<div class=\"header\"><span class=\"sender\" title=\"{}\">{}</span> <span class=\"date\">{}</span></div>\n",
+                escape_html(&msg.sender),
+                escape_html(&msg.sender_name),
+                format_date(&msg.date)
+            ));
+            if let Some(reply) = msg.in_reply_to {
+                body.push_str(&format!(
+                    "<div class=\"reply\">in reply to <a href=\"#{reply}\">{reply}</a></div>\n"
+                ));
+            }
+            body.push_str(&format!(
+                "<div class=\"text\" id=\"{}\">{}</div>\n",
+                msg.id,
+                escape_html(&msg.lines.join("\n"))
+            ));
+            for attachment in &msg.attachments {
+                let name = escape_html(&attachment.name);
+                match &attachment.path {
+                    Some(path) => body.push_str(&format!(
+                        "<div class=\"attachment\"><a href=\"{}\">{name}</a></div>\n",
+                        escape_html(path)
+                    )),
+                    None => body.push_str(&format!("<div class=\"attachment\">{name}</div>\n")),
+                }
+            }
+            if !msg.reactions.is_empty() {
+                let reactions: Vec<String> = msg
+                    .reactions
+                    .iter()
+                    .map(|r| format!("{} {}", escape_html(&r.emoji), r.count))
+                    .collect();
+                body.push_str(&format!(
+                    "<div class=\"reactions\">{}</div>\n",
+                    reactions.join(" ")
+                ));
+            }
+            body.push_str("</div>\n");
+        }
+
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{HTML_STYLE}</style>\n</head>\n<body>\n<h1>{title}</h1>\n<p class=\"date\">Exported {exported}</p>\n{body}</body>\n</html>\n",
+            title = escape_html(&self.title()),
+            exported = format_date(&self.exported_at),
+        )
+    }
+}
+
+const HTML_STYLE: &str = "body{font-family:sans-serif;max-width:50em;margin:auto;padding:1em}\
+.message{border-bottom:1px solid #ddd;padding:.5em 0}\
+.sender{font-weight:bold}.date{color:#777;font-size:.9em}\
+.text{white-space:pre-wrap;margin:.25em 0}.reply,.attachment,.reactions{font-size:.9em;color:#555}";
+
+pub fn parse(contents: &[u8]) -> Result<ConversationArchive, Error> {
+    let archive: ConversationArchive = serde_json::from_slice(contents)
+        .map_err(|e| Error::OtherWithContext(format!("invalid archive: {e}")))?;
+    if archive.version > ARCHIVE_VERSION {
+        return Err(Error::OtherWithContext(format!(
+            "archive version {} is newer than this version of the app supports",
+            archive.version
+        )));
+    }
+    Ok(archive)
+}
+
+/// reads an archive again after it was imported into `directory`. the export is the only json file in it
+pub fn read_imported(directory: &Path) -> Result<ConversationArchive, Error> {
+    let path = fs::read_dir(directory)?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| path.extension().map(|ext| ext == "json") == Some(true))
+        .ok_or_else(|| Error::OtherWithContext("archive not found".into()))?;
+    parse(&fs::read(path)?)
+}
+
+/// the name an attachment is saved under in the export. attachment names are chosen by the sender, so only the last
+/// component is kept and names that would point outside the attachments folder are rejected
+pub fn attachment_file_name(name: &str) -> Option<String> {
+    let name = Path::new(name).file_name()?.to_str()?;
+    if name.is_empty() || name == "." || name == ".." {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+fn format_date(date: &DateTime<Utc>) -> String {
+    date.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn archive() -> ConversationArchive {
+        let date = DateTime::parse_from_rfc3339("2023-03-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        ConversationArchive {
+            version: ARCHIVE_VERSION,
+            exported_at: date,
+            conversation_id: Uuid::nil(),
+            participants: vec![ArchivedParticipant {
+                did: "did:key:a".into(),
+                username: "alice".into(),
+            }],
+            messages: vec![ArchivedMessage {
+                id: Uuid::nil(),
+                sender: "did:key:a".into(),
+                sender_name: "alice".into(),
+                date,
+                in_reply_to: None,
+                lines: vec!["<script>hi</script>".into()],
+                reactions: vec![],
+                attachments: vec![ArchivedAttachment {
+                    name: "a b.png".into(),
+                    size: 3,
+                    path: Some("attachments/a b.png".into()),
+                }],
+            }],
+        }
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let archive = archive();
+        let json = archive.render(ExportFormat::Json).unwrap();
+        let parsed: ConversationArchive = serde_json::from_str(&json).unwrap();
+        assert_eq!(archive, parsed);
+    }
+
+    #[test]
+    fn html_escapes_message_text() {
+        let html = archive().render(ExportFormat::Html).unwrap();
+        assert!(html.contains("&lt;script&gt;hi&lt;/script&gt;"));
+        assert!(!html.contains("<script>"));
+    }
+
+    #[test]
+    fn markdown_links_bundled_attachments() {
+        let md = archive().render(ExportFormat::Markdown).unwrap();
+        assert!(md.contains("**alice** (2023-03-01 12:00:00 UTC)"));
+        assert!(md.contains("[a b.png](attachments/a%20b.png)"));
+    }
+
+    #[test]
+    fn attachment_names_stay_in_the_attachments_folder() {
+        assert_eq!(attachment_file_name("cat.png"), Some("cat.png".into()));
+        assert_eq!(attachment_file_name("../../x"), Some("x".into()));
+        assert_eq!(attachment_file_name("/etc/passwd"), Some("passwd".into()));
+        assert_eq!(attachment_file_name(".."), None);
+        assert_eq!(attachment_file_name(""), None);
+    }
+}
@@ -0,0 +1,170 @@
+//! finds uploads whose contents are already in Constellation. Constellation doesn't look files up by content, so
+//! the sha256 of every upload is saved here with where the file was put.
+//!
+//! files which were in Constellation before they could be indexed, or which were moved since, aren't found that
+//! way. when a file is checked, the files in Constellation of the same size which aren't indexed are read back and
+//! hashed. their hashes are kept, so each file is only read once.
+//!
+//! files can be moved or deleted afterwards, so an entry is only trusted if a file of the same size is still at
+//! that path. stale entries are dropped when they are found.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use futures::{stream::BoxStream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use warp::{error::Error, logging::tracing::log};
+
+use crate::STATIC_ARGS;
+
+/// what to do with an upload whose contents are already in Constellation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateAction {
+    // upload anyway. the new file is renamed if its name is taken
+    KeepBoth,
+    // don't upload. Constellation can't list a file in two directories, so there is no option to link to the
+    // existing file. configurations saved with that option skip, which is what it did
+    #[serde(alias = "link_existing")]
+    Skip,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ContentIndex {
+    // path in Constellation => (sha256, size)
+    files: HashMap<String, (String, usize)>,
+}
+
+impl ContentIndex {
+    pub fn load() -> Self {
+        fs::read(&STATIC_ARGS.content_index_path)
+            .ok()
+            .and_then(|b| serde_json::from_slice(&b).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        let contents = match serde_json::to_vec(self) {
+            Ok(c) => c,
+            Err(e) => {
+                log::error!("failed to serialize content index: {e}");
+                return;
+            }
+        };
+        if let Err(e) = fs::write(&STATIC_ARGS.content_index_path, contents) {
+            log::error!("failed to save content index: {e}");
+        }
+    }
+
+    /// `exists` checks that a file of the given size is at the path
+    pub fn find(&mut self, hash: &str, exists: impl Fn(&str, usize) -> bool) -> Option<String> {
+        let mut candidates: Vec<(String, usize)> = self
+            .files
+            .iter()
+            .filter(|(_, (h, _))| h == hash)
+            .map(|(path, (_, size))| (path.clone(), *size))
+            .collect();
+        candidates.sort();
+        for (path, size) in candidates {
+            if exists(&path, size) {
+                return Some(path);
+            }
+            self.files.remove(&path);
+        }
+        None
+    }
+
+    /// true if the file at `path` was hashed while it had this size
+    pub fn contains(&self, path: &str, size: usize) -> bool {
+        self.files.get(path).map(|(_, s)| *s == size) == Some(true)
+    }
+
+    pub fn insert(&mut self, hash: String, path: String, size: usize) {
+        self.files.insert(path, (hash, size));
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DuplicateUpload {
+    pub local_path: PathBuf,
+    // where the same contents are in Constellation
+    pub existing_path: String,
+}
+
+// files can be large, so they are hashed without reading them into memory
+pub fn hash_file(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+/// hashes a file which is read back from Constellation
+pub async fn hash_stream(
+    mut stream: BoxStream<'_, Result<Vec<u8>, Error>>,
+) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = stream.next().await {
+        hasher.update(&chunk?);
+    }
+    Ok(to_hex(&hasher.finalize()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stale_entries_are_dropped() {
+        let mut index = ContentIndex::default();
+        index.insert("abc".into(), "/photos/cat.png".into(), 10);
+
+        assert_eq!(
+            index.find("abc", |path, size| path == "/photos/cat.png" && size == 10),
+            Some("/photos/cat.png".into())
+        );
+        // the file was moved or deleted
+        assert_eq!(index.find("abc", |_, _| false), None);
+        assert_eq!(index.find("abc", |_, _| true), None);
+        assert!(!index.contains("/photos/cat.png", 10));
+    }
+
+    #[tokio::test]
+    async fn stored_files_hash_like_local_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a");
+        fs::write(&path, b"contents").unwrap();
+
+        let chunks: Vec<Result<Vec<u8>, Error>> = vec![Ok(b"cont".to_vec()), Ok(b"ents".to_vec())];
+        let hash = hash_stream(futures::stream::iter(chunks).boxed())
+            .await
+            .unwrap();
+        assert_eq!(hash, hash_file(&path).unwrap());
+    }
+
+    #[test]
+    fn same_contents_have_the_same_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a");
+        let b = dir.path().join("b");
+        fs::write(&a, b"contents").unwrap();
+        fs::write(&b, b"contents").unwrap();
+        assert_eq!(hash_file(&a).unwrap(), hash_file(&b).unwrap());
+    }
+}
@@ -0,0 +1,431 @@
+//! queues downloads of attachments and storage files, limits how many run at once, and reports their progress to
+//! the UI via WarpEvent::Download.
+//!
+//! files are written to `<destination>.part` and renamed once complete, so an interrupted download never looks like
+//! a finished file. if a storage file's `.part` file is left over from an earlier attempt, the bytes already on disk
+//! are kept. attachments are written by RayGun, which starts them over.
+//!
+//! nothing is transferred until the download leaves the queue: attachments are requested from RayGun then, and the
+//! Constellation streams are only read from then on.
+//!
+//! directories are downloaded as a zip file. their files are written to a temporary directory next to the
+//! destination, which is removed when the download finishes or is cancelled. the stream for each file is opened
+//! when the file before it is written, so a queued directory holds no streams.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use futures::{stream::BoxStream, StreamExt};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    fs,
+    io::{AsyncSeekExt, AsyncWriteExt},
+    sync::Semaphore,
+};
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+use warp::{
+    constellation::{ConstellationProgressStream, Progression},
+    error::Error,
+    logging::tracing::log,
+    raygun::RayGun,
+};
+
+use crate::{warp_runner::WarpEvent, WARP_EVENT_CH};
+
+use super::{manager::commands::compress_folder, Storage};
+
+pub const MAX_CONCURRENT_DOWNLOADS: usize = 3;
+
+static DOWNLOADS: Lazy<DownloadManager> = Lazy::new(|| DownloadManager {
+    permits: Semaphore::new(MAX_CONCURRENT_DOWNLOADS),
+    running: Mutex::new(HashMap::new()),
+});
+
+struct DownloadManager {
+    permits: Semaphore,
+    // lets a queued or running download be cancelled
+    running: Mutex<HashMap<Uuid, CancellationToken>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DownloadSource {
+    Attachment {
+        conversation_id: Uuid,
+        message_id: Uuid,
+    },
+    Storage,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DownloadInfo {
+    pub id: Uuid,
+    pub file_name: String,
+    pub destination: PathBuf,
+    pub source: DownloadSource,
+    // in bytes. used to detect files which were already downloaded
+    pub total: Option<usize>,
+}
+
+impl DownloadInfo {
+    pub fn new(file_name: String, destination: PathBuf, source: DownloadSource) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            file_name,
+            destination,
+            source,
+            total: None,
+        }
+    }
+
+    /// the download is written here and renamed to `destination` when complete
+    pub fn part_path(&self) -> PathBuf {
+        let mut name = self.destination.clone().into_os_string();
+        name.push(".part");
+        PathBuf::from(name)
+    }
+
+    /// true if `destination` already holds a file of the expected size. if the size isn't known the file can't be
+    /// assumed to be the same one, so it is downloaded again.
+    pub fn is_complete(&self) -> bool {
+        match (std::fs::metadata(&self.destination), self.total) {
+            (Ok(meta), Some(total)) => meta.is_file() && meta.len() as usize == total,
+            _ => false,
+        }
+    }
+}
+
+pub enum DownloadEvent {
+    Queued(DownloadInfo),
+    Progress {
+        id: Uuid,
+        current: usize,
+        total: Option<usize>,
+    },
+    Finished {
+        id: Uuid,
+    },
+    // the error is logged, not sent
+    Failed {
+        id: Uuid,
+    },
+    Cancelled {
+        id: Uuid,
+    },
+}
+
+/// where the contents of a download come from
+pub enum DownloadJob {
+    /// RayGun writes the file to `DownloadInfo::part_path` itself and only reports its progress
+    Attachment {
+        conversation_id: Uuid,
+        message_id: Uuid,
+        messaging: Box<dyn RayGun>,
+    },
+    /// Constellation hands over the contents, which are written here
+    Bytes(BoxStream<'static, Result<Vec<u8>, Error>>),
+    /// a directory in Constellation. `directories` are paths in the zip file. `files` pairs the path of each file in
+    /// storage with its path in the zip file
+    Directory {
+        storage: Storage,
+        directories: Vec<PathBuf>,
+        files: Vec<(PathBuf, PathBuf)>,
+    },
+}
+
+/// queues the download. it starts once fewer than MAX_CONCURRENT_DOWNLOADS are running.
+pub fn start(info: DownloadInfo, job: DownloadJob) {
+    let token = CancellationToken::new();
+    if let Ok(mut running) = DOWNLOADS.running.lock() {
+        running.insert(info.id, token.clone());
+    }
+    send_event(DownloadEvent::Queued(info.clone()));
+
+    tokio::spawn(async move {
+        let id = info.id;
+        let evt = match run(&info, job, &token).await {
+            Ok(true) => DownloadEvent::Finished { id },
+            Ok(false) => {
+                let _ = fs::remove_file(info.part_path()).await;
+                DownloadEvent::Cancelled { id }
+            }
+            Err(e) => {
+                log::error!("failed to download {}: {e}", info.file_name);
+                DownloadEvent::Failed { id }
+            }
+        };
+        if let Ok(mut running) = DOWNLOADS.running.lock() {
+            running.remove(&id);
+        }
+        send_event(evt);
+    });
+}
+
+/// used instead of `start` when `DownloadInfo::is_complete` returns true
+pub fn report_complete(info: DownloadInfo) {
+    let id = info.id;
+    send_event(DownloadEvent::Queued(info));
+    send_event(DownloadEvent::Finished { id });
+}
+
+/// returns false if the download isn't queued or running
+pub fn cancel(id: Uuid) -> bool {
+    match DOWNLOADS.running.lock() {
+        Ok(running) => match running.get(&id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        },
+        Err(_) => false,
+    }
+}
+
+// returns false if the download was cancelled
+async fn run(
+    info: &DownloadInfo,
+    job: DownloadJob,
+    token: &CancellationToken,
+) -> Result<bool, Error> {
+    let _permit = tokio::select! {
+        permit = DOWNLOADS.permits.acquire() => {
+            permit.map_err(|e| Error::OtherWithContext(e.to_string()))?
+        }
+        // still queued, so nothing was transferred yet
+        _ = token.cancelled() => return Ok(false),
+    };
+    log::debug!("starting download of {}", info.file_name);
+
+    let part_path = info.part_path();
+    let finished = match job {
+        DownloadJob::Attachment {
+            conversation_id,
+            message_id,
+            mut messaging,
+        } => {
+            let stream = messaging
+                .download(
+                    conversation_id,
+                    message_id,
+                    info.file_name.clone(),
+                    part_path.clone(),
+                )
+                .await?;
+            read_progress(info, stream, token).await?
+        }
+        // the streams are only read here, so dropping them stops the transfer
+        DownloadJob::Bytes(stream) => tokio::select! {
+            r = write_bytes(info, &part_path, stream) => r.map(|_| true)?,
+            _ = token.cancelled() => false,
+        },
+        DownloadJob::Directory {
+            storage,
+            directories,
+            files,
+        } => tokio::select! {
+            r = write_directory(info, &part_path, storage, directories, files) => r.map(|_| true)?,
+            _ = token.cancelled() => false,
+        },
+    };
+    if !finished {
+        return Ok(false);
+    }
+
+    fs::rename(&part_path, &info.destination).await?;
+    log::info!("{} downloaded", info.file_name);
+    Ok(true)
+}
+
+// RayGun has no way to stop a download once it started. when it is cancelled the progress is read to the end
+// without being reported, so that the part file isn't removed while RayGun is still writing to it. the download
+// keeps its place among the running ones until then. returns false if it was cancelled
+async fn read_progress(
+    info: &DownloadInfo,
+    mut stream: ConstellationProgressStream,
+    token: &CancellationToken,
+) -> Result<bool, Error> {
+    while let Some(progress) = stream.next().await {
+        let cancelled = token.is_cancelled();
+        match progress {
+            Progression::CurrentProgress { current, total, .. } if !cancelled => {
+                send_event(DownloadEvent::Progress {
+                    id: info.id,
+                    current,
+                    total: total.or(info.total),
+                });
+            }
+            Progression::CurrentProgress { .. } => {}
+            Progression::ProgressComplete { .. } => return Ok(!cancelled),
+            Progression::ProgressFailed { .. } if cancelled => return Ok(false),
+            Progression::ProgressFailed { error, .. } => {
+                return Err(Error::OtherWithContext(error.unwrap_or_default()));
+            }
+        }
+    }
+    Ok(!token.is_cancelled())
+}
+
+async fn write_bytes(
+    info: &DownloadInfo,
+    part_path: &Path,
+    mut stream: BoxStream<'static, Result<Vec<u8>, Error>>,
+) -> Result<(), Error> {
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .open(part_path)
+        .await?;
+
+    // keep what a previous attempt already wrote, unless it can't belong to this file
+    let mut existing = file.metadata().await?.len() as usize;
+    if info.total.map(|total| existing > total).unwrap_or(true) {
+        file.set_len(0).await?;
+        existing = 0;
+    }
+    file.seek(std::io::SeekFrom::Start(existing as u64)).await?;
+
+    let mut current = 0;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        let start = current;
+        current += chunk.len();
+        if current <= existing {
+            continue;
+        }
+        let skip = existing.saturating_sub(start);
+        file.write_all(&chunk[skip..]).await?;
+        send_event(DownloadEvent::Progress {
+            id: info.id,
+            current,
+            total: info.total,
+        });
+    }
+    file.flush().await?;
+
+    if current < existing {
+        return Err(Error::OtherWithContext(format!(
+            "{} is smaller than the partial download",
+            info.file_name
+        )));
+    }
+    Ok(())
+}
+
+// unlike write_bytes, nothing is kept from an earlier attempt
+async fn write_directory(
+    info: &DownloadInfo,
+    part_path: &Path,
+    mut storage: Storage,
+    directories: Vec<PathBuf>,
+    files: Vec<(PathBuf, PathBuf)>,
+) -> Result<(), Error> {
+    // next to the destination, so the zip file can be renamed into place
+    let parent = part_path.parent().unwrap_or_else(|| Path::new("."));
+    fs::create_dir_all(parent).await?;
+    let temp_dir = tempfile::Builder::new()
+        .prefix(".download")
+        .tempdir_in(parent)?;
+    let root = temp_dir.path().join("files");
+
+    for directory in directories {
+        fs::create_dir_all(root.join(directory)).await?;
+    }
+    let mut current = 0;
+    for (storage_path, zip_path) in files {
+        let name = storage_path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or(Error::InvalidItem)?;
+        // Constellation opens files in its current directory
+        storage.set_path(
+            storage_path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| PathBuf::from("/")),
+        );
+        let mut stream = storage.get_stream(&name).await?;
+
+        let path = root.join(zip_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let mut file = fs::File::create(&path).await?;
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            file.write_all(&chunk).await?;
+            current += chunk.len();
+            send_event(DownloadEvent::Progress {
+                id: info.id,
+                current,
+                total: info.total,
+            });
+        }
+        file.flush().await?;
+    }
+
+    let zip_path = temp_dir.path().join("download.zip");
+    compress_folder(root, zip_path.clone()).await?;
+    fs::rename(&zip_path, part_path).await?;
+    Ok(())
+}
+
+fn send_event(evt: DownloadEvent) {
+    if WARP_EVENT_CH.tx.send(WarpEvent::Download(evt)).is_err() {
+        log::error!("failed to send warp_event");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn part_path_appends_suffix() {
+        let info = DownloadInfo::new(
+            "a.txt".into(),
+            PathBuf::from("/tmp/downloads/a.txt"),
+            DownloadSource::Storage,
+        );
+        assert_eq!(info.part_path(), PathBuf::from("/tmp/downloads/a.txt.part"));
+    }
+
+    #[test]
+    fn is_complete_requires_matching_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let mut info = DownloadInfo::new("a.txt".into(), path, DownloadSource::Storage);
+        assert!(!info.is_complete());
+        info.total = Some(4);
+        assert!(!info.is_complete());
+        info.total = Some(5);
+        assert!(info.is_complete());
+    }
+
+    #[tokio::test]
+    async fn write_bytes_keeps_partial_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut info = DownloadInfo::new(
+            "a.txt".into(),
+            dir.path().join("a.txt"),
+            DownloadSource::Storage,
+        );
+        info.total = Some(11);
+        let part_path = info.part_path();
+        std::fs::write(&part_path, b"hello").unwrap();
+
+        let chunks: Vec<Result<Vec<u8>, Error>> =
+            vec![Ok(b"hel".to_vec()), Ok(b"lo world".to_vec())];
+        write_bytes(&info, &part_path, futures::stream::iter(chunks).boxed())
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read(&part_path).unwrap(), b"hello world");
+    }
+}
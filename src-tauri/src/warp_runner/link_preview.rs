@@ -0,0 +1,509 @@
+//! finds links in messages and fetches a title, description and image for each of them.
+//! previews are cached on disk, keyed by url, so a link is only fetched once no matter how many times it is sent.
+//! links which fail to load aren't cached, so they are tried again the next time they are sent.
+//!
+//! fetching a link tells the website that someone is reading it, so this is disabled unless
+//! `Configuration.privacy.link_previews` is set. State keeps the flag here in sync via `set_enabled`.
+//!
+//! links come from other people's messages. hosts which resolve to this machine or the local network are refused,
+//! so a message can't make Uplink send requests to services which aren't on the internet.
+
+use std::{
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+use warp::{error::Error, logging::tracing::log};
+
+use crate::{
+    warp_runner::{
+        ui_adapter::{message_text, MessageEvent},
+        WarpEvent,
+    },
+    STATIC_ARGS, WARP_EVENT_CH,
+};
+
+// don't spam websites if someone pastes a list of links
+const MAX_PREVIEWS_PER_MESSAGE: usize = 3;
+// only the <head> is needed. stop reading large pages early
+const MAX_BODY_SIZE: usize = 512 * 1024;
+const MAX_REDIRECTS: usize = 3;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+static HTTP_FETCHER: HttpFetcher = HttpFetcher;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+}
+
+impl LinkPreview {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.description.is_none() && self.image.is_none()
+    }
+}
+
+/// retrieves the html for a url. the default implementation uses HTTP. tests use a stub.
+pub trait PreviewFetcher: Send + Sync {
+    fn fetch<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<String, Error>>;
+}
+
+// redirects are followed by hand so that every host is checked. the client for each request only connects to the
+// addresses which were checked, so the host can't resolve to a different address in between
+pub struct HttpFetcher;
+
+impl PreviewFetcher for HttpFetcher {
+    fn fetch<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<String, Error>> {
+        Box::pin(async move {
+            let to_err = |e: reqwest::Error| Error::OtherWithContext(e.to_string());
+            let mut url = reqwest::Url::parse(url)
+                .map_err(|e| Error::OtherWithContext(format!("invalid url {url}: {e}")))?;
+
+            let mut redirects = 0;
+            let mut rsp = loop {
+                let host = url
+                    .host_str()
+                    .ok_or_else(|| Error::OtherWithContext(format!("{url} has no host")))?
+                    .to_string();
+                let addrs = public_addrs(&url).await?;
+                let client = reqwest::Client::builder()
+                    .timeout(Duration::from_secs(5))
+                    .redirect(reqwest::redirect::Policy::none())
+                    .user_agent("Uplink link preview")
+                    .resolve_to_addrs(&host, &addrs)
+                    .build()
+                    .map_err(to_err)?;
+                let rsp = client.get(url.clone()).send().await.map_err(to_err)?;
+                if !rsp.status().is_redirection() {
+                    break rsp;
+                }
+
+                redirects += 1;
+                if redirects > MAX_REDIRECTS {
+                    return Err(Error::OtherWithContext(format!(
+                        "too many redirects for {url}"
+                    )));
+                }
+                let location = rsp
+                    .headers()
+                    .get(reqwest::header::LOCATION)
+                    .and_then(|x| x.to_str().ok())
+                    .ok_or_else(|| Error::OtherWithContext(format!("{url} redirects nowhere")))?;
+                url = url
+                    .join(location)
+                    .map_err(|e| Error::OtherWithContext(format!("invalid redirect: {e}")))?;
+            };
+
+            let is_html = rsp
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|x| x.to_str().ok())
+                .map(|x| x.starts_with("text/html"))
+                .unwrap_or(false);
+            if !is_html {
+                return Err(Error::OtherWithContext(format!("{url} is not html")));
+            }
+
+            let mut body = Vec::new();
+            while let Some(chunk) = rsp.chunk().await.map_err(to_err)? {
+                body.extend_from_slice(&chunk);
+                if body.len() >= MAX_BODY_SIZE {
+                    break;
+                }
+            }
+            Ok(String::from_utf8_lossy(&body).to_string())
+        })
+    }
+}
+
+/// one json file per url, named after the hash of the url
+pub struct PreviewCache {
+    dir: PathBuf,
+}
+
+impl PreviewCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, url: &str) -> PathBuf {
+        let hash = Sha256::digest(url.as_bytes());
+        let name: String = hash.iter().map(|b| format!("{b:02x}")).collect();
+        self.dir.join(format!("{name}.json"))
+    }
+
+    pub async fn get(&self, url: &str) -> Option<LinkPreview> {
+        let bytes = tokio::fs::read(self.path(url)).await.ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    pub async fn insert(&self, preview: &LinkPreview) {
+        if let Err(e) = tokio::fs::create_dir_all(&self.dir).await {
+            log::error!("failed to create link preview cache: {e}");
+            return;
+        }
+        let contents = match serde_json::to_vec(preview) {
+            Ok(c) => c,
+            Err(e) => {
+                log::error!("failed to serialize link preview: {e}");
+                return;
+            }
+        };
+        if let Err(e) = tokio::fs::write(self.path(&preview.url), contents).await {
+            log::error!("failed to save link preview: {e}");
+        }
+    }
+}
+
+impl Default for PreviewCache {
+    fn default() -> Self {
+        Self::new(STATIC_ARGS.link_preview_cache_path.clone())
+    }
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// returns the previews which have already been fetched. doesn't touch the network.
+pub async fn cached_previews(lines: &[String], cache: &PreviewCache) -> Vec<LinkPreview> {
+    if !is_enabled() {
+        return vec![];
+    }
+    let mut previews = vec![];
+    for url in message_text::find_urls(lines)
+        .iter()
+        .take(MAX_PREVIEWS_PER_MESSAGE)
+    {
+        if let Some(preview) = cache.get(url).await.filter(|p| !p.is_empty()) {
+            previews.push(preview);
+        }
+    }
+    previews
+}
+
+/// returns a preview for each link in the message, fetching the ones which aren't cached yet.
+pub async fn fetch_previews(
+    lines: &[String],
+    fetcher: &dyn PreviewFetcher,
+    cache: &PreviewCache,
+) -> Vec<LinkPreview> {
+    let mut previews = vec![];
+    for url in message_text::find_urls(lines)
+        .into_iter()
+        .take(MAX_PREVIEWS_PER_MESSAGE)
+    {
+        let preview = match cache.get(&url).await {
+            Some(p) => p,
+            None => match fetcher.fetch(&url).await {
+                Ok(html) => {
+                    let preview = parse_preview(&url, &html);
+                    cache.insert(&preview).await;
+                    preview
+                }
+                Err(e) => {
+                    log::debug!("failed to fetch link preview for {url}: {e}");
+                    continue;
+                }
+            },
+        };
+        if !preview.is_empty() {
+            previews.push(preview);
+        }
+    }
+    previews
+}
+
+/// fetches the previews for a message in the background. when done, a MessageEvent::LinkPreviews is sent to the UI
+pub fn spawn_fetch(conversation_id: Uuid, message_id: Uuid, lines: Vec<String>) {
+    if !is_enabled() || message_text::find_urls(&lines).is_empty() {
+        return;
+    }
+    tokio::spawn(async move {
+        let cache = PreviewCache::default();
+        let previews = fetch_previews(&lines, &HTTP_FETCHER, &cache).await;
+        if previews.is_empty() {
+            return;
+        }
+        let evt = WarpEvent::Message(MessageEvent::LinkPreviews {
+            conversation_id,
+            message_id,
+            previews,
+        });
+        if WARP_EVENT_CH.tx.send(evt).is_err() {
+            log::error!("failed to send warp_event");
+        }
+    });
+}
+
+// resolves the host of `url`. fails if it resolves to an address which isn't on the internet
+async fn public_addrs(url: &reqwest::Url) -> Result<Vec<SocketAddr>, Error> {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(Error::OtherWithContext(format!("{url} isn't a web page")));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::OtherWithContext(format!("{url} has no host")))?;
+    // ipv6 hosts are in brackets in urls
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+    if addrs.is_empty() || !addrs.iter().all(|addr| is_public(addr.ip())) {
+        return Err(Error::OtherWithContext(format!(
+            "{host} isn't a public address"
+        )));
+    }
+    Ok(addrs)
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // 0.0.0.0/8 and the carrier-grade NAT range 100.64.0.0/10
+                || a == 0
+                || (a == 100 && (64..128).contains(&b)))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4() {
+                return is_public(IpAddr::V4(v4));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // unique local fc00::/7 and link-local fe80::/10
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// reads the OpenGraph tags, falling back to <title> and the description meta tag
+pub fn parse_preview(url: &str, html: &str) -> LinkPreview {
+    let mut title = None;
+    let mut og_title = None;
+    let mut description = None;
+    let mut og_description = None;
+    let mut image = None;
+
+    let lower = html.to_ascii_lowercase();
+    let mut pos = 0;
+    while let Some(start) = lower[pos..].find("<meta").map(|x| x + pos) {
+        let end = match lower[start..].find('>') {
+            Some(x) => start + x,
+            None => break,
+        };
+        let tag = &html[start..end];
+        pos = end;
+
+        let key = attribute(tag, "property").or_else(|| attribute(tag, "name"));
+        let content = attribute(tag, "content");
+        let (key, content) = match (key, content) {
+            (Some(k), Some(c)) if !c.trim().is_empty() => (k.to_lowercase(), c),
+            _ => continue,
+        };
+        match key.as_str() {
+            "og:title" => og_title = Some(content),
+            "og:description" => og_description = Some(content),
+            "description" => description = Some(content),
+            "og:image" | "og:image:url" if image.is_none() => image = Some(content),
+            _ => {}
+        }
+    }
+
+    if let Some(start) = lower.find("<title") {
+        if let Some(open_end) = lower[start..].find('>').map(|x| start + x + 1) {
+            if let Some(close) = lower[open_end..].find("</title>").map(|x| open_end + x) {
+                let t = unescape_html(html[open_end..close].trim());
+                if !t.is_empty() {
+                    title = Some(t);
+                }
+            }
+        }
+    }
+
+    LinkPreview {
+        url: url.to_string(),
+        title: og_title.or(title),
+        description: og_description.or(description),
+        image: image.and_then(|x| resolve_url(url, &x)),
+    }
+}
+
+// finds attr="value" or attr='value' within a tag
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut pos = 0;
+    while let Some(idx) = lower[pos..].find(name).map(|x| x + pos) {
+        pos = idx + name.len();
+        // make sure this isn't the end of a longer attribute name, e.g. data-name
+        let preceded_by_space = lower[..idx]
+            .chars()
+            .last()
+            .map(|c| c.is_whitespace())
+            .unwrap_or(false);
+        let rest = lower[pos..].trim_start();
+        if !preceded_by_space || !rest.starts_with('=') {
+            continue;
+        }
+        let value_start = tag.len() - rest.len() + 1;
+        let value = tag[value_start..].trim_start();
+        let quote = value.chars().next()?;
+        let value = if quote == '"' || quote == '\'' {
+            let value = &value[1..];
+            &value[..value.find(quote)?]
+        } else {
+            value.split_whitespace().next()?.trim_end_matches('/')
+        };
+        return Some(unescape_html(value));
+    }
+    None
+}
+
+fn unescape_html(input: &str) -> String {
+    input
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+// og:image is sometimes relative to the page
+fn resolve_url(page: &str, url: &str) -> Option<String> {
+    if url.starts_with("https://") || url.starts_with("http://") {
+        return Some(url.to_string());
+    }
+    let scheme_end = page.find("://")? + 3;
+    let scheme = &page[..scheme_end - 3];
+    if let Some(rest) = url.strip_prefix("//") {
+        return Some(format!("{scheme}://{rest}"));
+    }
+    if url.starts_with('/') {
+        let host_end = page[scheme_end..]
+            .find('/')
+            .map(|x| x + scheme_end)
+            .unwrap_or(page.len());
+        return Some(format!("{}{url}", &page[..host_end]));
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    struct StubFetcher {
+        pages: HashMap<String, String>,
+        requests: Mutex<Vec<String>>,
+    }
+
+    impl PreviewFetcher for StubFetcher {
+        fn fetch<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<String, Error>> {
+            Box::pin(async move {
+                self.requests.lock().unwrap().push(url.to_string());
+                self.pages.get(url).cloned().ok_or(Error::Other)
+            })
+        }
+    }
+
+    const PAGE: &str = r#"<html><head>
+        <title>Fallback &amp; Title</title>
+        <meta property="og:title" content="Example Domain" />
+        <meta name="description" content='An example page'>
+        <meta property="og:image" content="/logo.png">
+        </head><body></body></html>"#;
+
+    #[test]
+    fn parse_open_graph_tags() {
+        let preview = parse_preview("https://example.com/page", PAGE);
+        assert_eq!(
+            preview,
+            LinkPreview {
+                url: "https://example.com/page".into(),
+                title: Some("Example Domain".into()),
+                description: Some("An example page".into()),
+                image: Some("https://example.com/logo.png".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_title_fallback() {
+        let preview = parse_preview("https://example.com", "<title>Fallback &amp; Title</title>");
+        assert_eq!(preview.title, Some("Fallback & Title".into()));
+        assert_eq!(preview.image, None);
+    }
+
+    #[tokio::test]
+    async fn fetch_previews_uses_cache() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let cache = PreviewCache::new(dir.path().to_path_buf());
+        let fetcher = StubFetcher {
+            pages: HashMap::from([("https://example.com/page".to_string(), PAGE.to_string())]),
+            requests: Mutex::new(vec![]),
+        };
+        let lines = vec!["look at https://example.com/page and https://missing.example".into()];
+
+        let previews = fetch_previews(&lines, &fetcher, &cache).await;
+        assert_eq!(previews.len(), 1);
+        assert_eq!(previews[0].title, Some("Example Domain".into()));
+
+        // the second call is served from disk. the failed link is tried again
+        let again = fetch_previews(&lines, &fetcher, &cache).await;
+        assert_eq!(previews, again);
+        assert_eq!(
+            *fetcher.requests.lock().unwrap(),
+            vec![
+                "https://example.com/page".to_string(),
+                "https://missing.example".to_string(),
+                "https://missing.example".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn local_addresses_are_refused() {
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fe80::1",
+            "fd00::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+        assert!(is_public("93.184.216.34".parse().unwrap()));
+        assert!(is_public("2606:2800:220:1::".parse().unwrap()));
+    }
+}
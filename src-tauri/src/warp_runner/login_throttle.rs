@@ -0,0 +1,228 @@
+//! slows down guessing the passphrase. after a few failed unlocks, each further attempt has to wait twice as long
+//! as the one before. the policy can also lock the account for a while after too many failures.
+//!
+//! the failures are stored in login_config.json so that restarting Uplink doesn't reset them.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use warp::{error::Error, tesseract::Tesseract};
+
+use crate::state::configuration::{read_login_config, write_login_config};
+
+// the policy can be changed by editing this key in login_config.json
+const POLICY_KEY: &str = "login_policy";
+const ATTEMPTS_KEY: &str = "login_attempts";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoginPolicy {
+    // failures allowed before any delay is applied
+    #[serde(default = "free_attempts_default")]
+    pub free_attempts: u32,
+    #[serde(default = "base_delay_secs_default")]
+    pub base_delay_secs: u64,
+    #[serde(default = "max_delay_secs_default")]
+    pub max_delay_secs: u64,
+    // after this many failures, the account is locked for `lockout_secs`. None disables the lockout
+    #[serde(default)]
+    pub lockout_after: Option<u32>,
+    #[serde(default = "lockout_secs_default")]
+    pub lockout_secs: u64,
+}
+
+fn free_attempts_default() -> u32 {
+    3
+}
+
+fn base_delay_secs_default() -> u64 {
+    1
+}
+
+fn max_delay_secs_default() -> u64 {
+    5 * 60
+}
+
+fn lockout_secs_default() -> u64 {
+    30 * 60
+}
+
+impl Default for LoginPolicy {
+    fn default() -> Self {
+        Self {
+            free_attempts: free_attempts_default(),
+            base_delay_secs: base_delay_secs_default(),
+            max_delay_secs: max_delay_secs_default(),
+            lockout_after: None,
+            lockout_secs: lockout_secs_default(),
+        }
+    }
+}
+
+impl LoginPolicy {
+    pub fn load() -> Self {
+        read_login_config(POLICY_KEY).unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoginAttempts {
+    pub failures: u32,
+    pub last_failure: Option<DateTime<Utc>>,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+impl LoginAttempts {
+    pub fn load() -> Self {
+        read_login_config(ATTEMPTS_KEY).unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        write_login_config(ATTEMPTS_KEY, self);
+    }
+
+    /// fails if the next attempt isn't allowed yet
+    pub fn check(&self, policy: &LoginPolicy, now: DateTime<Utc>) -> Result<(), Error> {
+        match self.retry_after(policy, now) {
+            Some(wait) => Err(Error::OtherWithContext(format!(
+                "too many failed attempts. try again in {} seconds",
+                wait.as_secs()
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// returns how long to wait before the next attempt is allowed
+    pub fn retry_after(&self, policy: &LoginPolicy, now: DateTime<Utc>) -> Option<Duration> {
+        if let Some(locked_until) = self.locked_until {
+            if locked_until > now {
+                return (locked_until - now).to_std().ok();
+            }
+        }
+
+        let last_failure = self.last_failure?;
+        let delay = self.delay(policy)?;
+        let elapsed = (now - last_failure).to_std().unwrap_or_default();
+        delay.checked_sub(elapsed).filter(|d| !d.is_zero())
+    }
+
+    pub fn record_failure(&mut self, policy: &LoginPolicy, now: DateTime<Utc>) {
+        self.failures += 1;
+        self.last_failure = Some(now);
+        if let Some(lockout_after) = policy.lockout_after {
+            if self.failures >= lockout_after {
+                self.locked_until =
+                    Some(now + chrono::Duration::seconds(policy.lockout_secs as i64));
+                // the backoff starts over once the lockout ends
+                self.failures = 0;
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        if *self != Self::default() {
+            *self = Self::default();
+            self.save();
+        }
+    }
+
+    fn delay(&self, policy: &LoginPolicy) -> Option<Duration> {
+        let over = self.failures.checked_sub(policy.free_attempts)?;
+        if over == 0 {
+            return None;
+        }
+        let secs = policy
+            .base_delay_secs
+            .saturating_mul(2u64.saturating_pow(over - 1))
+            .min(policy.max_delay_secs);
+        Some(Duration::from_secs(secs))
+    }
+}
+
+/// unlocks tesseract after it was locked by auto-lock, or to confirm the passphrase. failures count towards the
+/// same backoff as logging in
+pub fn unlock(tesseract: &Tesseract, passphrase: &str) -> Result<(), Error> {
+    let policy = LoginPolicy::load();
+    let mut attempts = LoginAttempts::load();
+    attempts.check(&policy, Utc::now())?;
+    // unlock doesn't check the passphrase. decrypting the keypair does
+    let r = tesseract
+        .unlock(passphrase.as_bytes())
+        .and_then(|_| tesseract.retrieve("keypair").map(|_| ()));
+    match r {
+        Ok(_) => {
+            attempts.reset();
+            Ok(())
+        }
+        Err(e) => {
+            tesseract.lock();
+            attempts.record_failure(&policy, Utc::now());
+            attempts.save();
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fail(attempts: &mut LoginAttempts, policy: &LoginPolicy, times: u32, now: DateTime<Utc>) {
+        for _ in 0..times {
+            attempts.record_failure(policy, now);
+        }
+    }
+
+    #[test]
+    fn delay_doubles_after_free_attempts() {
+        let policy = LoginPolicy::default();
+        let now = Utc::now();
+        let mut attempts = LoginAttempts::default();
+
+        fail(&mut attempts, &policy, 3, now);
+        assert_eq!(attempts.retry_after(&policy, now), None);
+
+        fail(&mut attempts, &policy, 1, now);
+        assert_eq!(
+            attempts.retry_after(&policy, now),
+            Some(Duration::from_secs(1))
+        );
+        fail(&mut attempts, &policy, 2, now);
+        assert_eq!(
+            attempts.retry_after(&policy, now),
+            Some(Duration::from_secs(4))
+        );
+        let later = now + chrono::Duration::seconds(4);
+        assert_eq!(attempts.retry_after(&policy, later), None);
+    }
+
+    #[test]
+    fn delay_is_capped() {
+        let policy = LoginPolicy::default();
+        let now = Utc::now();
+        let mut attempts = LoginAttempts::default();
+        fail(&mut attempts, &policy, 40, now);
+        assert_eq!(
+            attempts.retry_after(&policy, now),
+            Some(Duration::from_secs(policy.max_delay_secs))
+        );
+    }
+
+    #[test]
+    fn lockout_after_too_many_failures() {
+        let policy = LoginPolicy {
+            lockout_after: Some(5),
+            ..Default::default()
+        };
+        let now = Utc::now();
+        let mut attempts = LoginAttempts::default();
+        fail(&mut attempts, &policy, 5, now);
+
+        assert_eq!(
+            attempts.retry_after(&policy, now),
+            Some(Duration::from_secs(policy.lockout_secs))
+        );
+        let after_lockout = now + chrono::Duration::seconds(policy.lockout_secs as i64);
+        assert_eq!(attempts.retry_after(&policy, after_lockout), None);
+    }
+}
@@ -1,22 +1,31 @@
 use std::{
     ffi::OsStr,
-    io::Read,
     path::{Path, PathBuf},
-    process::{Command, Stdio},
 };
 
+use chrono::{DateTime, Utc};
 use derive_more::Display;
 
 use futures::{channel::oneshot, StreamExt};
 use humansize::{format_size, DECIMAL};
 use mime::*;
-use once_cell::sync::Lazy;
-use tempfile::TempDir;
+use serde::Serialize;
 use tokio::sync::mpsc;
 use tokio_util::io::ReaderStream;
+use uuid::Uuid;
+use walkdir::WalkDir;
 
-use crate::state::storage::Storage as uplink_storage;
-use crate::warp_runner::Storage as warp_storage;
+use crate::state::storage::{
+    Breadcrumb, DirectoryListing, FileCategory, FileSearchResult, ItemStat,
+    Storage as uplink_storage, StorageStats,
+};
+use crate::warp_runner::{
+    content_index::{self, ContentIndex, DuplicateAction, DuplicateUpload},
+    downloads::{self, DownloadInfo, DownloadJob, DownloadSource},
+    thumbnails::{self, ThumbnailSize},
+    trash::{TrashEntry, TrashIndex, TRASH_DIRECTORY},
+    Storage as warp_storage,
+};
 
 use warp::{
     constellation::{
@@ -26,7 +35,6 @@ use warp::{
     },
     error::Error,
     logging::tracing::log,
-    sync::RwLock,
 };
 
 pub const VIDEO_FILE_EXTENSIONS: &[&str] = &[
@@ -38,14 +46,23 @@ pub const IMAGE_EXTENSIONS: &[&str] = &[
     ".bmp", ".svgz",
 ];
 
-static DIRECTORIES_AVAILABLE_TO_BROWSE: Lazy<RwLock<Vec<Directory>>> =
-    Lazy::new(|| RwLock::new(Vec::new()));
+pub const DOCUMENT_EXTENSIONS: &[&str] = &[
+    ".pdf", ".doc", ".docx", ".odt", ".rtf", ".txt", ".md", ".xls", ".xlsx", ".ods", ".csv",
+    ".ppt", ".pptx", ".odp", ".epub",
+];
+
+// how many files StorageStats lists as the largest
+const LARGEST_FILES_COUNT: usize = 10;
+const MAX_SEARCH_RESULTS: usize = 200;
 
 pub enum FileTransferStep {
     Start(String),
     DuplicateName(Option<String>),
     Upload(String),
     Thumbnail(Option<()>),
+    Folder(FolderUploadProgress),
+    // the file's contents are already in storage, at this path
+    DuplicateContent(String),
 }
 
 pub enum FileTransferProgress<T> {
@@ -54,122 +71,493 @@ pub enum FileTransferProgress<T> {
     Step(FileTransferStep),
 }
 
+// counts every file in the folder, including the ones which failed to upload
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct FolderUploadProgress {
+    pub files_done: usize,
+    pub files_total: usize,
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FolderUploadSummary {
+    #[serde(skip)]
+    pub storage: uplink_storage,
+    // the directory uploaded into, for the UI
+    pub listing: DirectoryListing,
+    pub progress: FolderUploadProgress,
+    pub files_uploaded: usize,
+    pub directories_created: usize,
+    // local files whose contents were already in storage. what was done with them depends on the DuplicateAction
+    pub duplicates: Vec<DuplicateUpload>,
+    // local files and folders which couldn't be uploaded, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+// paths start at the root directory, e.g. "/photos/2020" or "/photos/cat.png". every command says which directory or
+// item it works on, nothing depends on a directory opened by an earlier command
 #[derive(Display, Debug)]
 pub enum ConstellationCmd {
-    #[display(fmt = "GetItemsFromCurrentDirectory")]
-    GetItemsFromCurrentDirectory {
-        rsp: oneshot::Sender<Result<uplink_storage, warp::error::Error>>,
-    },
-    #[display(fmt = "CreateNewDirectory {{ directory_name: {directory_name} }} ")]
+    // creates a directory named `directory_name` in the directory at `path`
+    #[display(fmt = "CreateNewDirectory {{ path: {path}, directory_name: {directory_name} }} ")]
     CreateNewDirectory {
+        path: String,
         directory_name: String,
         rsp: oneshot::Sender<Result<(), warp::error::Error>>,
     },
-    #[display(fmt = "OpenDirectory {{ directory_name: {directory_name} }} ")]
-    OpenDirectory {
-        directory_name: String,
+    #[display(fmt = "ListDirectory {{ path: {path} }} ")]
+    ListDirectory {
+        path: String,
         rsp: oneshot::Sender<Result<uplink_storage, warp::error::Error>>,
     },
-    #[display(fmt = "BackToPreviousDirectory {{ directory: {directory:?} }} ")]
-    BackToPreviousDirectory {
-        directory: Directory,
-        rsp: oneshot::Sender<Result<uplink_storage, warp::error::Error>>,
+    #[display(fmt = "Stat {{ path: {path} }} ")]
+    Stat {
+        path: String,
+        rsp: oneshot::Sender<Result<ItemStat, warp::error::Error>>,
+    },
+    // returns the thumbnail of the file at `path` as a data url
+    #[display(fmt = "GetThumbnail {{ path: {path}, size: {size:?} }} ")]
+    GetThumbnail {
+        path: String,
+        size: ThumbnailSize,
+        rsp: oneshot::Sender<Result<String, warp::error::Error>>,
+    },
+    // searches every directory. see SearchFilter for how `query` is matched
+    #[display(fmt = "SearchFiles {{ query: {query}, kind: {kind:?} }} ")]
+    SearchFiles {
+        query: String,
+        kind: Option<FileCategory>,
+        min_size: Option<usize>,
+        modified_after: Option<DateTime<Utc>>,
+        rsp: oneshot::Sender<Result<Vec<FileSearchResult>, warp::error::Error>>,
+    },
+    #[display(fmt = "StorageStats")]
+    StorageStats {
+        rsp: oneshot::Sender<Result<StorageStats, warp::error::Error>>,
     },
-    #[display(fmt = "UploadFiles {{ files_path: {files_path:?} }} ")]
+    // finds the files, or files in the folders, whose contents are already in storage. used to ask the user what
+    // to do before uploading them
+    #[display(fmt = "FindDuplicates {{ files_path: {files_path:?} }} ")]
+    FindDuplicates {
+        files_path: Vec<PathBuf>,
+        rsp: oneshot::Sender<Result<Vec<DuplicateUpload>, warp::error::Error>>,
+    },
+    // uploads the files into the directory at `path`, and responds with its listing
+    #[display(
+        fmt = "UploadFiles {{ path: {path}, files_path: {files_path:?}, on_duplicate: {on_duplicate:?} }} "
+    )]
     UploadFiles {
+        path: String,
         files_path: Vec<PathBuf>,
+        on_duplicate: DuplicateAction,
         rsp: mpsc::UnboundedSender<FileTransferProgress<uplink_storage>>,
     },
-    #[display(fmt = "RenameItems {{ old_name: {old_name}, new_name: {new_name} }} ")]
+    // recreates the folder and its subfolders in the directory at `path`
+    #[display(
+        fmt = "UploadFolder {{ path: {path}, folder_path: {folder_path:?}, on_duplicate: {on_duplicate:?} }} "
+    )]
+    UploadFolder {
+        path: String,
+        folder_path: PathBuf,
+        on_duplicate: DuplicateAction,
+        rsp: mpsc::UnboundedSender<FileTransferProgress<FolderUploadSummary>>,
+    },
+    // renames the item at `path`, and responds with the listing of the directory it's in
+    #[display(fmt = "RenameItem {{ path: {path}, new_name: {new_name} }} ")]
     RenameItem {
-        old_name: String,
+        path: String,
         new_name: String,
         rsp: oneshot::Sender<Result<uplink_storage, warp::error::Error>>,
     },
+    // downloads the file at `path`
     #[display(
-        fmt = "DownloadItems {{ file_name: {file_name:?}, local_path_to_save_file: {local_path_to_save_file:?} }} "
+        fmt = "DownloadFile {{ path: {path}, local_path_to_save_file: {local_path_to_save_file:?} }} "
     )]
     DownloadFile {
-        file_name: String,
+        path: String,
         local_path_to_save_file: PathBuf,
-        rsp: oneshot::Sender<Result<(), warp::error::Error>>,
+        // the id of the queued download. progress is reported via WarpEvent::Download
+        rsp: oneshot::Sender<Result<Uuid, warp::error::Error>>,
+    },
+    // downloads the directory at `path`, e.g. "/photos", and everything in it as a zip file at `dest`. progress is
+    // reported via WarpEvent::Download, and the download is cancelled like any other
+    #[display(fmt = "DownloadDirectory {{ path: {path}, dest: {dest:?} }} ")]
+    DownloadDirectory {
+        path: String,
+        dest: PathBuf,
+        rsp: oneshot::Sender<Result<Uuid, warp::error::Error>>,
+    },
+    // the response has the listing of the destination and the items which couldn't be moved, with the reason
+    #[display(fmt = "MoveItems {{ source_paths: {source_paths:?}, destination: {destination} }} ")]
+    MoveItems {
+        source_paths: Vec<String>,
+        destination: String,
+        rsp: oneshot::Sender<Result<(uplink_storage, Vec<(String, String)>), warp::error::Error>>,
+    },
+    #[display(fmt = "CopyItems {{ source_paths: {source_paths:?}, destination: {destination} }} ")]
+    CopyItems {
+        source_paths: Vec<String>,
+        destination: String,
+        rsp: oneshot::Sender<Result<(uplink_storage, Vec<(String, String)>), warp::error::Error>>,
     },
-    #[display(fmt = "DeleteItems {{ item: {item:?} }} ")]
+    // moves the item at `path` into the trash, and responds with the listing of the directory it was in
+    #[display(fmt = "DeleteItems {{ path: {path} }} ")]
     DeleteItems {
-        item: Item,
+        path: String,
         rsp: oneshot::Sender<Result<uplink_storage, warp::error::Error>>,
     },
+    #[display(fmt = "ListTrash")]
+    ListTrash {
+        rsp: oneshot::Sender<Result<Vec<TrashEntry>, warp::error::Error>>,
+    },
+    // puts the items back where they were deleted from. if that directory is gone, they go in the root directory
+    #[display(fmt = "RestoreItems {{ ids: {ids:?} }} ")]
+    RestoreItems {
+        ids: Vec<Uuid>,
+        rsp: oneshot::Sender<Result<(), warp::error::Error>>,
+    },
+    #[display(fmt = "EmptyTrash")]
+    EmptyTrash {
+        rsp: oneshot::Sender<Result<(), warp::error::Error>>,
+    },
+    // removes the items deleted more than `retention_days` ago
+    #[display(fmt = "PurgeTrash {{ retention_days: {retention_days} }} ")]
+    PurgeTrash {
+        retention_days: u64,
+        rsp: oneshot::Sender<Result<(), warp::error::Error>>,
+    },
 }
 
 pub async fn handle_constellation_cmd(cmd: ConstellationCmd, warp_storage: &mut warp_storage) {
+    // some warp_storage calls only work in its current directory. commands set it with enter_directory when they need
+    // to, and every command starts from the root, so nothing carries over from the one before
+    warp_storage.set_path(PathBuf::from("/"));
     match cmd {
-        ConstellationCmd::GetItemsFromCurrentDirectory { rsp } => {
-            let r = get_items_from_current_directory(warp_storage);
-            let _ = rsp.send(r);
-        }
         ConstellationCmd::CreateNewDirectory {
+            path,
             directory_name,
             rsp,
         } => {
-            let r = create_new_directory(&directory_name, warp_storage).await;
+            let r = create_new_directory(warp_storage, &path, &directory_name).await;
             let _ = rsp.send(r);
         }
-        ConstellationCmd::OpenDirectory {
-            directory_name,
+        ConstellationCmd::ListDirectory { path, rsp } => {
+            let r = list_directory(warp_storage, &path);
+            let _ = rsp.send(r);
+        }
+        ConstellationCmd::Stat { path, rsp } => {
+            let r = stat_item(warp_storage, &path);
+            let _ = rsp.send(r);
+        }
+        ConstellationCmd::GetThumbnail { path, size, rsp } => {
+            let r = get_thumbnail(warp_storage, &path, size).await;
+            let _ = rsp.send(r);
+        }
+        ConstellationCmd::SearchFiles {
+            query,
+            kind,
+            min_size,
+            modified_after,
             rsp,
         } => {
-            let r = open_new_directory(warp_storage, &directory_name);
+            let filter = SearchFilter::new(&query, kind, min_size, modified_after);
+            let r = Ok(search_files(warp_storage, &filter));
+            let _ = rsp.send(r);
+        }
+        ConstellationCmd::StorageStats { rsp } => {
+            let r = Ok(storage_stats(warp_storage));
             let _ = rsp.send(r);
         }
-        ConstellationCmd::BackToPreviousDirectory { directory, rsp } => {
-            let r = go_back_to_previous_directory(warp_storage, directory);
+        ConstellationCmd::FindDuplicates { files_path, rsp } => {
+            let r = Ok(find_duplicates(warp_storage, files_path).await);
             let _ = rsp.send(r);
         }
-        ConstellationCmd::UploadFiles { files_path, rsp } => {
-            upload_files(warp_storage, files_path, rsp).await;
+        ConstellationCmd::UploadFiles {
+            path,
+            files_path,
+            on_duplicate,
+            rsp,
+        } => {
+            upload_files(warp_storage, &path, files_path, on_duplicate, rsp).await;
+        }
+        ConstellationCmd::UploadFolder {
+            path,
+            folder_path,
+            on_duplicate,
+            rsp,
+        } => {
+            upload_folder(warp_storage, &path, folder_path, on_duplicate, rsp).await;
         }
         ConstellationCmd::DownloadFile {
-            file_name,
+            path,
             local_path_to_save_file,
             rsp,
         } => {
-            let r = download_file(warp_storage, file_name, local_path_to_save_file).await;
+            let r = download_file(warp_storage, &path, local_path_to_save_file).await;
+            let _ = rsp.send(r);
+        }
+        ConstellationCmd::DownloadDirectory { path, dest, rsp } => {
+            let r = download_directory(warp_storage, &path, dest).await;
             let _ = rsp.send(r);
         }
         ConstellationCmd::RenameItem {
-            old_name,
+            path,
             new_name,
             rsp,
         } => {
-            let r = rename_item(old_name, new_name, warp_storage).await;
+            let r = rename_item(warp_storage, &path, &new_name).await;
+            let _ = rsp.send(r);
+        }
+        ConstellationCmd::MoveItems {
+            source_paths,
+            destination,
+            rsp,
+        } => {
+            let r = move_items(warp_storage, source_paths, destination).await;
+            let _ = rsp.send(r);
+        }
+        ConstellationCmd::CopyItems {
+            source_paths,
+            destination,
+            rsp,
+        } => {
+            let r = copy_items(warp_storage, source_paths, destination).await;
+            let _ = rsp.send(r);
+        }
+        ConstellationCmd::DeleteItems { path, rsp } => {
+            let r = delete_items(warp_storage, &path);
+            let _ = rsp.send(r);
+        }
+        ConstellationCmd::ListTrash { rsp } => {
+            let r = list_trash(warp_storage);
+            let _ = rsp.send(r);
+        }
+        ConstellationCmd::RestoreItems { ids, rsp } => {
+            let r = restore_items(warp_storage, ids);
             let _ = rsp.send(r);
         }
-        ConstellationCmd::DeleteItems { item, rsp } => {
-            let r = delete_items(warp_storage, item).await;
+        ConstellationCmd::EmptyTrash { rsp } => {
+            let r = empty_trash(warp_storage).await;
+            let _ = rsp.send(r);
+        }
+        ConstellationCmd::PurgeTrash {
+            retention_days,
+            rsp,
+        } => {
+            let r = purge_trash(warp_storage, retention_days).await;
             let _ = rsp.send(r);
         }
     }
 }
 
-async fn delete_items(
-    warp_storage: &mut warp_storage,
-    item: Item,
-) -> Result<uplink_storage, Error> {
+// the item is renamed to the id of its TrashEntry and moved into the trash
+fn delete_items(warp_storage: &mut warp_storage, path: &str) -> Result<uplink_storage, Error> {
+    check_not_trash(path)?;
+    let (parent, name) = split_item_path(path).ok_or(Error::InvalidItem)?;
+    let trash = trash_directory(warp_storage)?;
+    let current_directory = resolve_directory(warp_storage, &parent)?;
+    let item = current_directory.get_item(&name)?;
+    let entry = TrashEntry {
+        id: Uuid::new_v4(),
+        name: item.name(),
+        original_directory: parent.clone(),
+        deleted_at: Utc::now(),
+        size: item.size(),
+        is_directory: item.is_directory(),
+    };
+
+    let item = current_directory.remove_item(&entry.name)?;
+    item.rename(&entry.id.to_string())?;
+    if let Err(error) = trash.add_item(item.clone()) {
+        // put it back where it was
+        item.rename(&entry.name)?;
+        current_directory.add_item(item)?;
+        return Err(error);
+    }
+    log::info!("Moved {} to the trash", entry.name);
+    let mut index = TrashIndex::load();
+    index.insert(entry);
+    index.save();
+    list_directory(warp_storage, &parent)
+}
+
+// saves the index when the trash is created, so load the index after calling this
+fn trash_directory(warp_storage: &warp_storage) -> Result<Directory, Error> {
+    let root = warp_storage.root_directory();
+    let mut index = TrashIndex::load();
+    if let Ok(item) = root.get_item(TRASH_DIRECTORY) {
+        if index.directory_id() == Some(item.id()) {
+            return item.get_directory();
+        }
+        // the user's own folder, made before the name was reserved. it is kept under another name
+        let new_name = rename_if_duplicate(
+            root.clone(),
+            TRASH_DIRECTORY.to_string(),
+            PathBuf::from(TRASH_DIRECTORY),
+        );
+        item.rename(&new_name)?;
+        log::warn!("renamed /{TRASH_DIRECTORY} to /{new_name}, the name is used by the trash");
+    }
+    let trash = Directory::new(TRASH_DIRECTORY);
+    root.add_item(trash.clone())?;
+    index.set_directory_id(trash.id());
+    index.save();
+    Ok(trash)
+}
+
+// the trash can only be changed with the trash commands
+fn check_not_trash(path: &str) -> Result<(), Error> {
+    if is_trash(path) {
+        return Err(Error::OtherWithContext(format!(
+            "/{TRASH_DIRECTORY} is reserved for the trash"
+        )));
+    }
+    Ok(())
+}
+
+// entries whose item is no longer in the trash are dropped
+fn list_trash(warp_storage: &warp_storage) -> Result<Vec<TrashEntry>, Error> {
+    let trash = trash_directory(warp_storage)?;
+    let mut index = TrashIndex::load();
+    index.retain(|entry| trash.get_item(&entry.id.to_string()).is_ok());
+    index.save();
+    let mut entries = index.entries().to_vec();
+    entries.sort_by_key(|e| std::cmp::Reverse(e.deleted_at));
+    Ok(entries)
+}
+
+fn restore_items(warp_storage: &mut warp_storage, ids: Vec<Uuid>) -> Result<(), Error> {
+    let trash = trash_directory(warp_storage)?;
+    let mut index = TrashIndex::load();
+    for id in ids {
+        let entry = match index.remove(id) {
+            Some(e) => e,
+            None => {
+                log::warn!("{id} isn't in the trash");
+                continue;
+            }
+        };
+        let destination = resolve_directory(warp_storage, &entry.original_directory)
+            .unwrap_or_else(|_| {
+                log::warn!(
+                    "{} no longer exists. restoring {} to the root directory",
+                    entry.original_directory,
+                    entry.name
+                );
+                warp_storage.root_directory()
+            });
+        if let Err(error) = restore_item(&trash, &destination, &entry) {
+            log::error!("Failed to restore {}: {error}", entry.name);
+            index.insert(entry);
+        }
+    }
+    index.save();
+    Ok(())
+}
+
+fn restore_item(
+    trash: &Directory,
+    destination: &Directory,
+    entry: &TrashEntry,
+) -> Result<(), Error> {
+    let trash_name = entry.id.to_string();
+    let name = rename_if_duplicate(
+        destination.clone(),
+        entry.name.clone(),
+        PathBuf::from(&entry.name),
+    );
+    let item = trash.remove_item(&trash_name)?;
+    item.rename(&name)?;
+    if let Err(error) = destination.add_item(item.clone()) {
+        item.rename(&trash_name)?;
+        trash.add_item(item)?;
+        return Err(error);
+    }
+    log::info!("Restored {name} to {}", entry.original_directory);
+    Ok(())
+}
+
+// items in the trash without an entry are removed too
+async fn empty_trash(warp_storage: &mut warp_storage) -> Result<(), Error> {
+    let names = trash_directory(warp_storage)?
+        .get_items()
+        .iter()
+        .map(|item| item.name())
+        .collect::<Vec<_>>();
+    remove_from_trash(warp_storage, &names).await?;
+    let mut index = TrashIndex::load();
+    index.clear();
+    index.save();
+    Ok(())
+}
+
+// 0 days keeps everything
+async fn purge_trash(warp_storage: &mut warp_storage, retention_days: u64) -> Result<(), Error> {
+    if retention_days == 0 {
+        return Ok(());
+    }
+    let expired = TrashIndex::load().expired(Utc::now(), retention_days);
+    if expired.is_empty() {
+        return Ok(());
+    }
+    let names = expired.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+    let r = remove_from_trash(warp_storage, &names).await;
+    // entries whose item is gone can't be restored, whether or not removing the rest failed
+    let trash = trash_directory(warp_storage)?;
+    let mut index = TrashIndex::load();
+    index.retain(|entry| trash.get_item(&entry.id.to_string()).is_ok());
+    index.save();
+    log::info!("removed {} items from the trash", expired.len());
+    r
+}
+
+async fn remove_from_trash(warp_storage: &mut warp_storage, names: &[String]) -> Result<(), Error> {
+    let original_path = warp_storage.get_path().to_path_buf();
+    warp_storage.set_path(PathBuf::from(format!("/{TRASH_DIRECTORY}")));
+    let mut r = Ok(());
+    for name in names {
+        let trash = match warp_storage.current_directory() {
+            Ok(trash) => trash,
+            Err(error) => {
+                r = Err(error);
+                break;
+            }
+        };
+        let item = match trash.get_item(name) {
+            Ok(item) => item,
+            // already removed
+            Err(_) => continue,
+        };
+        if let Err(error) = delete_permanently(warp_storage, item).await {
+            r = Err(error);
+            break;
+        }
+    }
+    warp_storage.set_path(original_path);
+    r
+}
+
+// removes the item from the current directory. directories are emptied first
+async fn delete_permanently(warp_storage: &mut warp_storage, item: Item) -> Result<(), Error> {
     // If is file, just a small function solve it
     if item.is_file() {
         let file_name = item.name();
-        match warp_storage.remove(&file_name, false).await {
-            Ok(_) => log::info!("File deleted: {:?}", file_name),
-            Err(error) => log::error!("Error to delete file {:?}, {:?}", file_name, error),
+        return match warp_storage.remove(&file_name, false).await {
+            Ok(_) => {
+                log::info!("File deleted: {:?}", file_name);
+                Ok(())
+            }
+            Err(error) => {
+                log::error!("Error to delete file {:?}, {:?}", file_name, error);
+                Err(error)
+            }
         };
-        return get_items_from_current_directory(warp_storage);
     };
     // Code keeps here just if item is a directory
     let first_dir = warp_storage.current_directory()?;
-    let mut current_dirs_opened = get_directories_opened();
-    current_dirs_opened.push(first_dir.clone());
-
-    let mut dirs: Vec<Directory> = current_dirs_opened;
+    let mut dirs: Vec<Directory> =
+        directories_along_path(warp_storage, &current_path(warp_storage))?;
 
     match warp_storage.select(&item.name()) {
         Ok(_) => log::debug!("Selected new dir: {:?}.", item.name()),
@@ -182,7 +570,6 @@ async fn delete_items(
 
     while let Some(last_dir) = dirs.clone().last() {
         if last_dir.id() == first_dir.id() {
-            set_new_directory_opened(dirs.as_mut(), last_dir.clone());
             break;
         };
 
@@ -252,43 +639,272 @@ async fn delete_items(
                     warp_storage.current_directory()?.name()
                 ),
                 Err(error) => {
-                    log::error!("Error to delete this file: {:?}, {:?}", file.name(), error)
+                    log::error!("Error to delete this file: {:?}, {:?}", file.name(), error);
+                    return Err(error);
                 }
             };
         }
     }
-    get_items_from_current_directory(warp_storage)
+    Ok(())
 }
 
 async fn rename_item(
-    old_name: String,
-    new_name: String,
     warp_storage: &mut warp_storage,
+    path: &str,
+    new_name: &str,
 ) -> Result<uplink_storage, Error> {
-    if let Err(error) = warp_storage.rename(&old_name, &new_name).await {
+    let (parent, old_name) = split_item_path(path).ok_or(Error::InvalidItem)?;
+    check_not_trash(path)?;
+    check_not_trash(&join_item_path(&parent, new_name))?;
+    enter_directory(warp_storage, &parent)?;
+    if let Err(error) = warp_storage.rename(&old_name, new_name).await {
         log::error!("Failed to rename item: {error}");
+        return Err(error);
+    }
+    list_directory(warp_storage, &parent)
+}
+
+// items keep their id when moved. if the destination already has an item with the same name, the moved item is
+// renamed like an upload would be. returns the items which couldn't be moved, with the reason
+async fn move_items(
+    warp_storage: &mut warp_storage,
+    source_paths: Vec<String>,
+    destination: String,
+) -> Result<(uplink_storage, Vec<(String, String)>), Error> {
+    check_not_trash(&destination)?;
+    let dest_dir = resolve_directory(warp_storage, &destination)?;
+    let mut failed = vec![];
+    for source in source_paths {
+        // items are taken out of the trash with RestoreItems
+        if let Err(error) = check_not_trash(&source) {
+            failed.push((source, error.to_string()));
+            continue;
+        }
+        if let Err(error) = move_item(warp_storage, &source, &destination, &dest_dir) {
+            log::error!("Failed to move {source} to {destination}: {error}");
+            failed.push((source, error.to_string()));
+        }
+    }
+    Ok((list_directory(warp_storage, &destination)?, failed))
+}
+
+fn move_item(
+    warp_storage: &warp_storage,
+    source: &str,
+    destination: &str,
+    dest_dir: &Directory,
+) -> Result<(), Error> {
+    let (parent, name) = split_item_path(source).ok_or(Error::InvalidItem)?;
+    let parent_dir = resolve_directory(warp_storage, &parent)?;
+    if parent_dir.id() == dest_dir.id() {
+        return Ok(());
+    }
+    if parent_dir.get_item(&name)?.is_directory() && is_within(destination, source) {
+        return Err(Error::OtherWithContext(format!(
+            "can't move {source} into itself"
+        )));
+    }
+
+    let new_name = rename_if_duplicate(dest_dir.clone(), name.clone(), PathBuf::from(&name));
+    let item = parent_dir.remove_item(&name)?;
+    let moved = (|| {
+        if new_name != name {
+            item.rename(&new_name)?;
+        }
+        dest_dir.add_item(item.clone())
+    })();
+    if let Err(error) = moved {
+        // put it back where it was, so it isn't left outside of every directory. if it can't get its name back it
+        // is put back under the new one
+        if item.name() != name {
+            if let Err(e) = item.rename(&name) {
+                log::error!("failed to rename {source} back: {e}");
+            }
+        }
+        if let Err(e) = parent_dir.add_item(item) {
+            log::error!("failed to put {source} back after a failed move: {e}");
+        }
+        return Err(error);
+    }
+    log::info!("Moved {source} to {destination}/{new_name}");
+    Ok(())
+}
+
+// files are copied by streaming them into a new file. directories are copied with everything in them. returns the
+// items which couldn't be copied, with the reason
+async fn copy_items(
+    warp_storage: &mut warp_storage,
+    source_paths: Vec<String>,
+    destination: String,
+) -> Result<(uplink_storage, Vec<(String, String)>), Error> {
+    check_not_trash(&destination)?;
+    resolve_directory(warp_storage, &destination)?;
+    // copying changes the current directory
+    let original_path = warp_storage.get_path().to_path_buf();
+    let mut failed = vec![];
+    for source in source_paths {
+        if let Err(error) = check_not_trash(&source) {
+            failed.push((source, error.to_string()));
+            continue;
+        }
+        if let Err(error) = copy_item(warp_storage, &source, &destination).await {
+            log::error!("Failed to copy {source} to {destination}: {error}");
+            failed.push((source, error.to_string()));
+        }
+    }
+    warp_storage.set_path(original_path);
+    Ok((list_directory(warp_storage, &destination)?, failed))
+}
+
+async fn copy_item(
+    warp_storage: &mut warp_storage,
+    source: &str,
+    destination: &str,
+) -> Result<(), Error> {
+    let (parent, name) = split_item_path(source).ok_or(Error::InvalidItem)?;
+    let item = resolve_directory(warp_storage, &parent)?.get_item(&name)?;
+    if item.is_directory() && is_within(destination, source) {
+        return Err(Error::OtherWithContext(format!(
+            "can't copy {source} into itself"
+        )));
+    }
+
+    // (item to copy, the path of the directory it's in, the path of the directory to copy it to)
+    let mut pending = vec![(item, parent, destination.to_string())];
+    while let Some((item, src_parent, dest_path)) = pending.pop() {
+        let dest_dir = resolve_directory(warp_storage, &dest_path)?;
+        let new_name = rename_if_duplicate(dest_dir, item.name(), PathBuf::from(item.name()));
+        if item.is_directory() {
+            warp_storage.set_path(PathBuf::from(&dest_path));
+            warp_storage.create_directory(&new_name, false).await?;
+            let src_path = join_item_path(&src_parent, &item.name());
+            let new_path = join_item_path(&dest_path, &new_name);
+            for child in item.get_directory()?.get_items() {
+                pending.push((child, src_path.clone(), new_path.clone()));
+            }
+        } else {
+            copy_file(warp_storage, &item, &src_parent, &dest_path, &new_name).await?;
+        }
+    }
+    log::info!("Copied {source} to {destination}");
+    Ok(())
+}
+
+async fn copy_file(
+    warp_storage: &mut warp_storage,
+    item: &Item,
+    src_parent: &str,
+    dest_path: &str,
+    new_name: &str,
+) -> Result<(), Error> {
+    warp_storage.set_path(PathBuf::from(src_parent));
+    let stream = warp_storage.get_stream(&item.name()).await?;
+    warp_storage.set_path(PathBuf::from(dest_path));
+
+    let size = item.size();
+    let mut progress = warp_storage
+        .put_stream(
+            new_name,
+            Some(size),
+            stream.filter_map(|x| async { x.ok() }).boxed(),
+        )
+        .await?;
+    let mut written = 0;
+    while let Some(p) = progress.next().await {
+        match p {
+            Progression::ProgressComplete { total, .. } => written = total.unwrap_or_default(),
+            Progression::ProgressFailed { error, .. } => {
+                return Err(Error::OtherWithContext(error.unwrap_or_default()))
+            }
+            Progression::CurrentProgress { .. } => {}
+        }
+    }
+    // the stream skips chunks which failed to read, so a short copy is incomplete
+    if written != size {
+        let _ = warp_storage.remove(new_name, false).await;
+        return Err(Error::OtherWithContext(format!(
+            "copied {written} of {size} bytes"
+        )));
     }
 
-    get_items_from_current_directory(warp_storage)
+    let thumbnail = item.thumbnail();
+    if !thumbnail.is_empty() {
+        warp_storage
+            .current_directory()?
+            .get_item(new_name)?
+            .set_thumbnail(&thumbnail);
+    }
+    Ok(())
+}
+
+// paths start at the root directory, e.g. "/photos/cat.png". "/" is the root directory
+fn resolve_directory(warp_storage: &warp_storage, path: &str) -> Result<Directory, Error> {
+    let root = warp_storage.root_directory();
+    let path = path.trim_matches('/');
+    if path.is_empty() {
+        return Ok(root);
+    }
+    root.get_item_by_path(path)?.get_directory()
+}
+
+// splits "/photos/cat.png" into "/photos" and "cat.png"
+fn split_item_path(path: &str) -> Option<(String, String)> {
+    let path = path.trim_matches('/');
+    let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+    if name.is_empty() {
+        return None;
+    }
+    Some((format!("/{parent}"), name.to_string()))
+}
+
+fn join_item_path(parent: &str, name: &str) -> String {
+    format!("{}/{name}", parent.trim_end_matches('/'))
+}
+
+fn is_trash(path: &str) -> bool {
+    is_within(path, TRASH_DIRECTORY)
+}
+
+// true if `path` is `directory` or inside it
+fn is_within(path: &str, directory: &str) -> bool {
+    let path = path.trim_matches('/');
+    let directory = directory.trim_matches('/');
+    path == directory || path.starts_with(&format!("{directory}/"))
 }
 
 async fn create_new_directory(
-    folder_name: &str,
     warp_storage: &mut warp_storage,
+    path: &str,
+    folder_name: &str,
 ) -> Result<(), Error> {
+    check_not_trash(&join_item_path(path, folder_name))?;
+    enter_directory(warp_storage, path)?;
     warp_storage.create_directory(folder_name, true).await?;
     log::debug!("New directory created: {:?}", folder_name);
     Ok(())
 }
 
-fn get_items_from_current_directory(
-    warp_storage: &mut warp_storage,
-) -> Result<uplink_storage, Error> {
-    let current_dir = warp_storage.current_directory()?;
-    let mut current_dirs = get_directories_opened();
-    set_new_directory_opened(current_dirs.as_mut(), current_dir.clone());
+// makes the directory at `path` warp_storage's current directory, for the calls which only work there. it is reset
+// to the root before the next command
+fn enter_directory(warp_storage: &mut warp_storage, path: &str) -> Result<Directory, Error> {
+    let dir = resolve_directory(warp_storage, path)?;
+    warp_storage.set_path(PathBuf::from(normalize_path(path)));
+    Ok(dir)
+}
 
-    let items = current_dir.get_items();
+// doesn't change anything, so any number of directories can be listed at once
+fn list_directory(warp_storage: &warp_storage, path: &str) -> Result<uplink_storage, Error> {
+    let directories_opened = directories_along_path(warp_storage, path)?;
+    let current_dir = directories_opened
+        .last()
+        .cloned()
+        .unwrap_or_else(|| warp_storage.root_directory());
+
+    let items = current_dir
+        .get_items()
+        .into_iter()
+        .filter(|item| !is_trash(&join_item_path(path, &item.name())))
+        .collect::<Vec<_>>();
 
     let mut directories = items
         .iter()
@@ -302,87 +918,277 @@ fn get_items_from_current_directory(
     directories.sort_by_key(|b| std::cmp::Reverse(b.modified()));
     files.sort_by_key(|b| std::cmp::Reverse(b.modified()));
 
-    let uplink_storage = uplink_storage {
+    Ok(uplink_storage {
         initialized: true,
         current_dir,
-        directories_opened: get_directories_opened(),
+        path: normalize_path(path),
+        breadcrumbs: breadcrumbs(&directories_opened),
+        directories_opened,
         directories,
         files,
-    };
-    log::info!("Get items from current directory worked!");
-    Ok(uplink_storage)
+    })
 }
 
-fn get_directories_opened() -> Vec<Directory> {
-    DIRECTORIES_AVAILABLE_TO_BROWSE.read().to_owned()
+fn stat_item(warp_storage: &warp_storage, path: &str) -> Result<ItemStat, Error> {
+    let path = normalize_path(path);
+    let (item, mut directories) = match split_item_path(&path) {
+        Some((parent, name)) => {
+            let directories = directories_along_path(warp_storage, &parent)?;
+            let parent_dir = directories.last().ok_or(Error::InvalidItem)?;
+            (parent_dir.get_item(&name)?, directories)
+        }
+        None => (Item::from(warp_storage.root_directory()), vec![]),
+    };
+    // a directory's breadcrumbs end with itself, a file's with the directory it's in
+    if let Ok(dir) = item.get_directory() {
+        directories.push(dir);
+    }
+    Ok(ItemStat {
+        name: item.name(),
+        is_directory: item.is_directory(),
+        size: item.size(),
+        created: item.creation(),
+        modified: item.modified(),
+        breadcrumbs: breadcrumbs(&directories),
+        path,
+    })
 }
 
-fn set_new_directory_opened(current_dir: &mut Vec<Directory>, new_dir: Directory) {
-    if !current_dir.contains(&new_dir) {
-        log::debug!("Updating directories opened to browse");
-        current_dir.push(new_dir);
-        *DIRECTORIES_AVAILABLE_TO_BROWSE.write() = current_dir.to_owned()
+// only the large size is stored in Constellation, the others are made from it
+async fn get_thumbnail(
+    warp_storage: &warp_storage,
+    path: &str,
+    size: ThumbnailSize,
+) -> Result<String, Error> {
+    let (parent, name) = split_item_path(&normalize_path(path)).ok_or(Error::InvalidItem)?;
+    let directories = directories_along_path(warp_storage, &parent)?;
+    let parent_dir = directories.last().ok_or(Error::InvalidItem)?;
+    let thumbnail = parent_dir.get_item(&name)?.thumbnail();
+    if thumbnail.is_empty() {
+        return Err(Error::OtherWithContext(format!("{path} has no thumbnail")));
     }
+    tokio::task::spawn_blocking(move || {
+        thumbnails::resize_thumbnail(&thumbnail, size).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| Error::OtherWithContext(e.to_string()))?
+    .map_err(Error::OtherWithContext)
 }
 
-fn open_new_directory(
-    warp_storage: &mut warp_storage,
-    folder_name: &str,
-) -> Result<uplink_storage, Error> {
-    let current_path = PathBuf::from(
-        warp_storage
-            .get_path()
-            .join(folder_name)
-            .to_string_lossy()
-            .replace('\\', "/"),
-    );
+fn storage_stats(warp_storage: &warp_storage) -> StorageStats {
+    let mut stats = StorageStats::default();
+    let mut folders: Vec<(String, usize)> = vec![];
+    let mut files: Vec<(String, usize)> = vec![];
+
+    // (directory, its path, the index in `folders` of the top-level folder it's in)
+    let mut pending = vec![(warp_storage.root_directory(), String::from("/"), None)];
+    while let Some((dir, path, folder)) = pending.pop() {
+        for item in dir.get_items() {
+            let item_path = join_item_path(&path, &item.name());
+            // the trash isn't a folder of the user's. it counts towards the total, since it takes up space
+            if item.is_directory() && is_trash(&item_path) {
+                stats.trash_size += item.size();
+                stats.total_size += item.size();
+                continue;
+            }
+            if let Ok(sub_dir) = item.get_directory() {
+                let folder = folder.or_else(|| {
+                    folders.push((item.name(), 0));
+                    Some(folders.len() - 1)
+                });
+                pending.push((sub_dir, item_path, folder));
+                continue;
+            }
+            let size = item.size();
+            stats.total_size += size;
+            stats.file_count += 1;
+            if let Some(idx) = folder {
+                folders[idx].1 += size;
+            }
+            stats
+                .by_type
+                .add(FileCategory::from_name(&item.name()), size);
+            files.push((item_path, size));
+        }
+    }
 
-    warp_storage.set_path(current_path);
+    folders.sort_by_key(|(_, size)| std::cmp::Reverse(*size));
+    stats.folders = folders;
+    stats.largest_files = largest(files, LARGEST_FILES_COUNT);
+    stats.readable_total = format_size(stats.total_size, DECIMAL);
+    let quota = warp_storage.max_size();
+    if quota > 0 {
+        stats.quota = Some(quota);
+        stats.remaining = Some(quota.saturating_sub(stats.total_size));
+    }
+    stats
+}
 
-    log::info!(
-        "Navigation to directory {:?} worked!",
-        warp_storage.get_path()
-    );
-    get_items_from_current_directory(warp_storage)
+// `query` is matched against file names, ignoring case:
+// - "" matches every file
+// - ".png" matches the extension
+// - "image/png" or "image/*" matches the MIME type, which is guessed from the extension
+// - anything else matches part of the name
+struct SearchFilter {
+    query: String,
+    mime: Option<Mime>,
+    kind: Option<FileCategory>,
+    min_size: Option<usize>,
+    modified_after: Option<DateTime<Utc>>,
 }
 
-fn go_back_to_previous_directory(
-    warp_storage: &mut warp_storage,
-    directory: Directory,
-) -> Result<uplink_storage, Error> {
-    let mut current_dirs = get_directories_opened();
-    loop {
-        let current_dir = warp_storage.current_directory()?;
+impl SearchFilter {
+    fn new(
+        query: &str,
+        kind: Option<FileCategory>,
+        min_size: Option<usize>,
+        modified_after: Option<DateTime<Utc>>,
+    ) -> Self {
+        let query = query.trim().to_lowercase();
+        let mime = if query.contains('/') {
+            query.parse::<Mime>().ok()
+        } else {
+            None
+        };
+        Self {
+            query,
+            mime,
+            kind,
+            min_size,
+            modified_after,
+        }
+    }
 
-        current_dirs.remove(current_dirs.len() - 1);
+    fn matches(&self, name: &str, size: usize, modified: DateTime<Utc>) -> bool {
+        let name = name.to_lowercase();
+        let category = FileCategory::from_name(&name);
+        let extension = name
+            .rsplit_once('.')
+            .map(|(_, ext)| ext)
+            .unwrap_or_default();
+
+        if self.kind.map_or(false, |kind| kind != category)
+            || self.min_size.map_or(false, |min| size < min)
+            || self.modified_after.map_or(false, |after| modified <= after)
+        {
+            return false;
+        }
+        if let Some(mime) = &self.mime {
+            let type_matches = if mime.type_() == IMAGE {
+                category == FileCategory::Image
+            } else if mime.type_() == VIDEO {
+                category == FileCategory::Video
+            } else {
+                true
+            };
+            let subtype = mime.subtype().as_str();
+            let subtype_matches =
+                subtype == "*" || subtype == extension || (subtype == "jpeg" && extension == "jpg");
+            return type_matches && subtype_matches;
+        }
+        match self.query.strip_prefix('.') {
+            Some(ext) => ext == extension,
+            None => name.contains(&self.query),
+        }
+    }
+}
 
-        if current_dir.id() == directory.id() {
-            set_new_directory_opened(current_dirs.as_mut(), current_dir);
-            break;
+// the most recently modified files first
+fn search_files(warp_storage: &warp_storage, filter: &SearchFilter) -> Vec<FileSearchResult> {
+    let mut results = vec![];
+    let mut pending = vec![(warp_storage.root_directory(), String::from("/"))];
+    while let Some((dir, path)) = pending.pop() {
+        for item in dir.get_items() {
+            let item_path = join_item_path(&path, &item.name());
+            if let Ok(sub_dir) = item.get_directory() {
+                if !is_trash(&item_path) {
+                    pending.push((sub_dir, item_path));
+                }
+                continue;
+            }
+            if !filter.matches(&item.name(), item.size(), item.modified()) {
+                continue;
+            }
+            results.push(FileSearchResult {
+                name: item.name(),
+                kind: FileCategory::from_name(&item.name()),
+                size: item.size(),
+                modified: item.modified(),
+                directory: path.clone(),
+                path: item_path,
+            });
         }
+    }
+    results.sort_by_key(|r| std::cmp::Reverse(r.modified));
+    results.truncate(MAX_SEARCH_RESULTS);
+    results
+}
 
-        if let Err(error) = warp_storage.go_back() {
-            log::error!("Error on go back a directory: {error}");
-            return Err(error);
-        };
+// the `count` largest files, largest first
+fn largest(mut files: Vec<(String, usize)>, count: usize) -> Vec<(String, usize)> {
+    files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    files.truncate(count);
+    files
+}
+
+// the root directory, followed by every directory in `path`
+fn directories_along_path(
+    warp_storage: &warp_storage,
+    path: &str,
+) -> Result<Vec<Directory>, Error> {
+    let mut dir = warp_storage.root_directory();
+    let mut dirs = vec![dir.clone()];
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        dir = dir.get_item(name)?.get_directory()?;
+        dirs.push(dir.clone());
     }
-    log::info!("Navigation to directory {} worked!", directory.name());
-    get_items_from_current_directory(warp_storage)
+    Ok(dirs)
+}
+
+// `directories` starts at the root directory
+fn breadcrumbs(directories: &[Directory]) -> Vec<Breadcrumb> {
+    let mut path = String::new();
+    directories
+        .iter()
+        .enumerate()
+        .map(|(idx, dir)| {
+            if idx > 0 {
+                path = join_item_path(&path, &dir.name());
+            }
+            Breadcrumb {
+                name: dir.name(),
+                path: normalize_path(&path),
+            }
+        })
+        .collect()
+}
+
+fn current_path(warp_storage: &warp_storage) -> String {
+    normalize_path(&warp_storage.get_path().to_string_lossy().replace('\\', "/"))
+}
+
+// "photos/2020/" becomes "/photos/2020"
+fn normalize_path(path: &str) -> String {
+    format!("/{}", path.trim_matches('/'))
 }
 
 async fn upload_files(
     warp_storage: &mut warp_storage,
+    path: &str,
     files_path: Vec<PathBuf>,
+    on_duplicate: DuplicateAction,
     // todo: send FileTransferProgress::Step until done
     tx: mpsc::UnboundedSender<FileTransferProgress<uplink_storage>>,
 ) {
-    let current_directory = match warp_storage.current_directory() {
-        Ok(d) => d,
-        Err(e) => {
-            let _ = tx.send(FileTransferProgress::Error(e));
-            return;
-        }
-    };
+    let current_directory =
+        match check_not_trash(path).and_then(|_| enter_directory(warp_storage, path)) {
+            Ok(d) => d,
+            Err(e) => {
+                let _ = tx.send(FileTransferProgress::Error(e));
+                return;
+            }
+        };
+    let mut index = ContentIndex::load();
     for file_path in files_path {
         let mut filename = match file_path
             .file_name()
@@ -391,7 +1197,6 @@ async fn upload_files(
             Some(file) => file,
             None => continue,
         };
-        let local_path = Path::new(&file_path).to_string_lossy().to_string();
 
         let _ = tx.send(FileTransferProgress::Step(FileTransferStep::Start(
             filename.clone(),
@@ -406,149 +1211,483 @@ async fn upload_files(
         let _ = tx.send(FileTransferProgress::Step(FileTransferStep::DuplicateName(
             Some(filename.clone()),
         )));
-        let tokio_file = match tokio::fs::File::open(&local_path).await {
-            Ok(file) => file,
-            Err(error) => {
-                log::error!("Error on get tokio file, cancelling upload action, error: {error}");
+
+        let hash = hash_upload(&file_path).await;
+        let existing = match &hash {
+            Some((hash, size)) => find_existing(warp_storage, &mut index, hash, *size).await,
+            None => None,
+        };
+        if let Some(existing) = existing {
+            let _ = tx.send(FileTransferProgress::Step(
+                FileTransferStep::DuplicateContent(existing.clone()),
+            ));
+            if skip_duplicate(&existing, &filename, on_duplicate) {
+                continue;
+            }
+        }
+
+        match upload_file(warp_storage, &file_path, &filename, &tx, |_| {}).await {
+            Ok(_) => {
+                log::info!("{:?} file uploaded!", filename);
+                record_upload(warp_storage, &mut index, hash, &filename);
+            }
+            Err(error) => log::error!("Error when upload file: {:?}", error),
+        }
+    }
+    index.save();
+    let ret = match list_directory(warp_storage, path) {
+        Ok(r) => FileTransferProgress::Finished(r),
+        Err(e) => FileTransferProgress::Error(e),
+    };
+    let _ = tx.send(ret);
+}
+
+// uploads the folder and everything in it into the directory at `path`. a file which fails to upload doesn't stop the
+// rest, it is listed in the summary instead
+async fn upload_folder(
+    warp_storage: &mut warp_storage,
+    path: &str,
+    folder_path: PathBuf,
+    on_duplicate: DuplicateAction,
+    tx: mpsc::UnboundedSender<FileTransferProgress<FolderUploadSummary>>,
+) {
+    if let Err(e) = check_not_trash(path).and_then(|_| enter_directory(warp_storage, path)) {
+        let _ = tx.send(FileTransferProgress::Error(e));
+        return;
+    }
+    let mut index = ContentIndex::load();
+    let mut progress = FolderUploadProgress::default();
+    for entry in WalkDir::new(&folder_path).into_iter().flatten() {
+        if entry.file_type().is_file() {
+            progress.files_total += 1;
+            progress.bytes_total += entry.metadata().map(|m| m.len()).unwrap_or_default();
+        }
+    }
+    let _ = tx.send(FileTransferProgress::Step(FileTransferStep::Folder(
+        progress.clone(),
+    )));
+
+    let mut failed: Vec<(PathBuf, String)> = vec![];
+    let mut duplicates: Vec<DuplicateUpload> = vec![];
+    let mut directories_created = 0;
+    let mut files_uploaded = 0;
+    // how many directories below the starting directory warp_storage is in
+    let mut depth = 0;
+    let mut walker = WalkDir::new(&folder_path).sort_by_file_name().into_iter();
+    while let Some(entry) = walker.next() {
+        let entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                let path = e.path().map(Path::to_path_buf).unwrap_or_default();
+                failed.push((path, e.to_string()));
                 continue;
             }
         };
+        // leave the directories which the walk is done with. an entry at depth n goes into the nth directory
+        while depth > entry.depth() {
+            if let Err(e) = warp_storage.go_back() {
+                let _ = tx.send(FileTransferProgress::Error(e));
+                return;
+            }
+            depth -= 1;
+        }
+        let local_name = entry.file_name().to_string_lossy().to_string();
+        let current_directory = match warp_storage.current_directory() {
+            Ok(d) => d,
+            Err(e) => {
+                let _ = tx.send(FileTransferProgress::Error(e));
+                return;
+            }
+        };
+        let name = rename_if_duplicate(
+            current_directory,
+            local_name.clone(),
+            PathBuf::from(&local_name),
+        );
+
+        if entry.file_type().is_dir() {
+            let r = match warp_storage.create_directory(&name, false).await {
+                Ok(_) => warp_storage.select(&name),
+                Err(e) => Err(e),
+            };
+            match r {
+                Ok(_) => {
+                    directories_created += 1;
+                    depth += 1;
+                }
+                Err(e) => {
+                    log::error!("failed to create directory {name}: {e}");
+                    failed.push((entry.into_path(), e.to_string()));
+                    walker.skip_current_dir();
+                }
+            }
+            continue;
+        }
+        // symlinks aren't followed
+        if !entry.file_type().is_file() {
+            continue;
+        }
 
-        let total_size_for_stream = match tokio_file.metadata().await {
-            Ok(data) => Some(data.len() as usize),
-            Err(error) => {
-                log::error!("Error getting metadata: {:?}", error);
-                None
+        let _ = tx.send(FileTransferProgress::Step(FileTransferStep::Start(
+            name.clone(),
+        )));
+        let bytes_before = progress.bytes_done;
+        let hash = hash_upload(entry.path()).await;
+        let existing = match &hash {
+            Some((hash, size)) => find_existing(warp_storage, &mut index, hash, *size).await,
+            None => None,
+        };
+        let skip = match existing {
+            Some(existing) => {
+                let _ = tx.send(FileTransferProgress::Step(
+                    FileTransferStep::DuplicateContent(existing.clone()),
+                ));
+                let duplicate = DuplicateUpload {
+                    local_path: entry.path().to_path_buf(),
+                    existing_path: existing.clone(),
+                };
+                duplicates.push(duplicate);
+                skip_duplicate(&existing, &name, on_duplicate)
             }
+            None => false,
+        };
+        // true if the file was uploaded
+        let r = if skip {
+            Ok(false)
+        } else {
+            upload_file(warp_storage, entry.path(), &name, &tx, |current| {
+                progress.bytes_done = bytes_before + current as u64;
+                let _ = tx.send(FileTransferProgress::Step(FileTransferStep::Folder(
+                    progress.clone(),
+                )));
+            })
+            .await
+            .map(|_| true)
         };
+        progress.bytes_done = bytes_before + entry.metadata().map(|m| m.len()).unwrap_or_default();
+        progress.files_done += 1;
+        let _ = tx.send(FileTransferProgress::Step(FileTransferStep::Folder(
+            progress.clone(),
+        )));
+        match r {
+            Ok(false) => {}
+            Ok(true) => {
+                files_uploaded += 1;
+                record_upload(warp_storage, &mut index, hash, &name);
+            }
+            Err(e) => {
+                log::error!("failed to upload {}: {e}", entry.path().display());
+                failed.push((entry.into_path(), e.to_string()));
+            }
+        }
+    }
+    for _ in 0..depth {
+        if let Err(e) = warp_storage.go_back() {
+            let _ = tx.send(FileTransferProgress::Error(e));
+            return;
+        }
+    }
+    index.save();
 
-        let file_stream = ReaderStream::new(tokio_file)
-            .filter_map(|x| async { x.ok() })
-            .map(|x| x.into());
+    log::info!(
+        "uploaded {} of {} files from {}",
+        files_uploaded,
+        progress.files_total,
+        folder_path.display()
+    );
+    let ret = match list_directory(warp_storage, path) {
+        Ok(storage) => FileTransferProgress::Finished(FolderUploadSummary {
+            listing: DirectoryListing::from(&storage),
+            storage,
+            progress,
+            files_uploaded,
+            directories_created,
+            duplicates,
+            failed,
+        }),
+        Err(e) => FileTransferProgress::Error(e),
+    };
+    let _ = tx.send(ret);
+}
 
-        match warp_storage
-            .put_stream(&filename, total_size_for_stream, file_stream.boxed())
-            .await
-        {
-            Ok(mut upload_progress) => {
-                let mut previous_percentage: usize = 0;
-                let mut upload_process_started = false;
-
-                while let Some(upload_progress) = upload_progress.next().await {
-                    match upload_progress {
-                        Progression::CurrentProgress {
-                            name,
-                            current,
-                            total,
-                        } => {
-                            if !upload_process_started {
-                                upload_process_started = true;
-                                log::info!("Starting upload for {name}");
-                                log::info!("0% completed -> written 0 bytes")
-                            };
-
-                            if let Some(total) = total {
-                                let current_percentage =
-                                    (((current as f64) / (total as f64)) * 100.) as usize;
-                                if previous_percentage != current_percentage {
-                                    previous_percentage = current_percentage;
-                                    let readable_current = format_size(current, DECIMAL);
-                                    let percentage_number =
-                                        ((current as f64) / (total as f64)) * 100.;
-
-                                    let _ = tx.send(FileTransferProgress::Step(
-                                        FileTransferStep::Upload(format!(
-                                            "{}%",
-                                            percentage_number as usize
-                                        )),
-                                    ));
-
-                                    log::info!(
-                                        "{}% completed -> written {readable_current}",
-                                        percentage_number as usize
-                                    )
-                                }
-                            }
-                        }
-                        Progression::ProgressComplete { name, total } => {
-                            let total = total.unwrap_or_default();
-                            let readable_total = format_size(total, DECIMAL);
-                            let _ = tx.send(FileTransferProgress::Step(FileTransferStep::Upload(
-                                readable_total.clone(),
-                            )));
-                            log::info!("{name} has been uploaded with {}", readable_total);
-                        }
-                        Progression::ProgressFailed {
-                            name,
-                            last_size,
-                            error,
-                        } => {
-                            log::info!(
-                                "{name} failed to upload at {} MB due to: {}",
-                                last_size.unwrap_or_default(),
-                                error.unwrap_or_default()
-                            );
-                        }
-                    }
-                }
+async fn find_duplicates(
+    warp_storage: &mut warp_storage,
+    files_path: Vec<PathBuf>,
+) -> Vec<DuplicateUpload> {
+    let mut index = ContentIndex::load();
+    let mut duplicates = vec![];
+    for path in files_path {
+        for entry in WalkDir::new(path).into_iter().flatten() {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let existing = match hash_upload(entry.path()).await {
+                Some((hash, size)) => find_existing(warp_storage, &mut index, &hash, size).await,
+                None => None,
+            };
+            if let Some(existing_path) = existing {
+                duplicates.push(DuplicateUpload {
+                    local_path: entry.into_path(),
+                    existing_path,
+                });
+            }
+        }
+    }
+    // stale entries may have been dropped and stored files hashed
+    index.save();
+    duplicates
+}
 
-                let video_formats = VIDEO_FILE_EXTENSIONS.to_vec();
-                let image_formats = IMAGE_EXTENSIONS.to_vec();
-
-                let file_extension = std::path::Path::new(&filename)
-                    .extension()
-                    .and_then(OsStr::to_str)
-                    .map(|s| format!(".{s}"))
-                    .unwrap_or_default();
-
-                if image_formats.iter().any(|f| f == &file_extension) {
-                    match set_thumbnail_if_file_is_image(warp_storage, filename.clone()).await {
-                        Ok(_) => {
-                            log::info!("Image Thumbnail uploaded");
-                            let _ = tx.send(FileTransferProgress::Step(
-                                FileTransferStep::Thumbnail(Some(())),
-                            ));
-                        }
-                        Err(error) => {
-                            log::error!("Not possible to update thumbnail for image: {:?}", error);
-                            let _ = tx.send(FileTransferProgress::Step(
-                                FileTransferStep::Thumbnail(None),
-                            ));
-                        }
-                    };
-                }
+// returns the hash and size of a local file. files can be large, so they are hashed off the warp_runner task
+async fn hash_upload(file_path: &Path) -> Option<(String, usize)> {
+    let path = file_path.to_path_buf();
+    let r = tokio::task::spawn_blocking(move || {
+        let size = path.metadata()?.len() as usize;
+        content_index::hash_file(&path).map(|hash| (hash, size))
+    })
+    .await;
+    match r {
+        Ok(Ok(hashed)) => Some(hashed),
+        Ok(Err(e)) => {
+            log::warn!("failed to hash {}: {e}", file_path.display());
+            None
+        }
+        Err(e) => {
+            log::error!("failed to hash {}: {e}", file_path.display());
+            None
+        }
+    }
+}
 
-                if video_formats.iter().any(|f| f == &file_extension) {
-                    match set_thumbnail_if_file_is_video(
-                        warp_storage,
-                        filename.clone(),
-                        file_path.clone(),
-                    ) {
-                        Ok(_) => {
-                            log::info!("Video Thumbnail uploaded");
-                            let _ = tx.send(FileTransferProgress::Step(
-                                FileTransferStep::Thumbnail(Some(())),
-                            ));
-                        }
-                        Err(error) => {
-                            log::error!("Not possible to update thumbnail for video: {:?}", error);
-                            let _ = tx.send(FileTransferProgress::Step(
-                                FileTransferStep::Thumbnail(None),
-                            ));
-                        }
-                    };
+// returns the path of a file in storage with the same contents. files of the same size which aren't in the index
+// are read back and hashed, see content_index
+async fn find_existing(
+    warp_storage: &mut warp_storage,
+    index: &mut ContentIndex,
+    hash: &str,
+    size: usize,
+) -> Option<String> {
+    let found = index.find(hash, |path, size| {
+        split_item_path(path)
+            .and_then(|(parent, name)| {
+                resolve_directory(warp_storage, &parent)
+                    .ok()?
+                    .get_item(&name)
+                    .ok()
+            })
+            .map(|item| !item.is_directory() && item.size() == size)
+            .unwrap_or(false)
+    });
+    if found.is_some() {
+        return found;
+    }
+
+    let candidates: Vec<String> = files_of_size(warp_storage, size)
+        .into_iter()
+        .filter(|path| !index.contains(path, size))
+        .collect();
+    // getting a stream changes the current directory
+    let original_path = warp_storage.get_path().to_path_buf();
+    let mut found = None;
+    for path in candidates {
+        match hash_stored_file(warp_storage, &path).await {
+            Ok(stored_hash) => {
+                let matches = stored_hash == hash;
+                index.insert(stored_hash, path.clone(), size);
+                if matches {
+                    found = Some(path);
+                    break;
                 }
+            }
+            Err(e) => log::warn!("failed to hash {path}: {e}"),
+        }
+    }
+    warp_storage.set_path(original_path);
+    found
+}
 
-                log::info!("{:?} file uploaded!", filename);
+// the paths of the files outside the trash with this size
+fn files_of_size(warp_storage: &warp_storage, size: usize) -> Vec<String> {
+    let mut paths = vec![];
+    let mut pending = vec![(warp_storage.root_directory(), String::from("/"))];
+    while let Some((dir, path)) = pending.pop() {
+        for item in dir.get_items() {
+            let item_path = join_item_path(&path, &item.name());
+            match item.get_directory() {
+                Ok(sub_dir) if !is_trash(&item_path) => pending.push((sub_dir, item_path)),
+                Ok(_) => {}
+                Err(_) if item.size() == size => paths.push(item_path),
+                Err(_) => {}
             }
-            Err(error) => log::error!("Error when upload file: {:?}", error),
         }
     }
-    let ret = match get_items_from_current_directory(warp_storage) {
-        Ok(r) => FileTransferProgress::Finished(r),
-        Err(e) => FileTransferProgress::Error(e),
+    paths
+}
+
+async fn hash_stored_file(warp_storage: &mut warp_storage, path: &str) -> Result<String, Error> {
+    let (parent, name) = split_item_path(path).ok_or(Error::InvalidItem)?;
+    warp_storage.set_path(PathBuf::from(parent));
+    let stream = warp_storage.get_stream(&name).await?;
+    content_index::hash_stream(stream).await
+}
+
+// returns true if the file shouldn't be uploaded because its contents are already at `existing`
+fn skip_duplicate(existing: &str, name: &str, on_duplicate: DuplicateAction) -> bool {
+    match on_duplicate {
+        DuplicateAction::KeepBoth => false,
+        DuplicateAction::Skip => {
+            log::info!("skipped {name}, its contents are already at {existing}");
+            true
+        }
+    }
+}
+
+// remembers where an uploaded file was put, so the next upload of the same contents is found
+fn record_upload(
+    warp_storage: &warp_storage,
+    index: &mut ContentIndex,
+    hash: Option<(String, usize)>,
+    name: &str,
+) {
+    if let Some((hash, size)) = hash {
+        let path = join_item_path(&current_path(warp_storage), name);
+        index.insert(hash, path, size);
+    }
+}
+
+// uploads a single file into the current directory and sets its thumbnail. `on_progress` receives the bytes
+// written so far
+async fn upload_file<T>(
+    warp_storage: &mut warp_storage,
+    file_path: &Path,
+    filename: &str,
+    tx: &mpsc::UnboundedSender<FileTransferProgress<T>>,
+    mut on_progress: impl FnMut(usize),
+) -> Result<(), Error> {
+    let tokio_file = tokio::fs::File::open(file_path).await?;
+
+    let total_size_for_stream = match tokio_file.metadata().await {
+        Ok(data) => Some(data.len() as usize),
+        Err(error) => {
+            log::error!("Error getting metadata: {:?}", error);
+            None
+        }
     };
-    let _ = tx.send(ret);
+
+    let file_stream = ReaderStream::new(tokio_file)
+        .filter_map(|x| async { x.ok() })
+        .map(|x| x.into());
+
+    let mut upload_progress = warp_storage
+        .put_stream(filename, total_size_for_stream, file_stream.boxed())
+        .await?;
+    let mut previous_percentage: usize = 0;
+    let mut upload_process_started = false;
+
+    while let Some(upload_progress) = upload_progress.next().await {
+        match upload_progress {
+            Progression::CurrentProgress {
+                name,
+                current,
+                total,
+            } => {
+                if !upload_process_started {
+                    upload_process_started = true;
+                    log::info!("Starting upload for {name}");
+                    log::info!("0% completed -> written 0 bytes")
+                };
+                on_progress(current);
+
+                if let Some(total) = total {
+                    let current_percentage = (((current as f64) / (total as f64)) * 100.) as usize;
+                    if previous_percentage != current_percentage {
+                        previous_percentage = current_percentage;
+                        let readable_current = format_size(current, DECIMAL);
+                        let percentage_number = ((current as f64) / (total as f64)) * 100.;
+
+                        let _ = tx.send(FileTransferProgress::Step(FileTransferStep::Upload(
+                            format!("{}%", percentage_number as usize),
+                        )));
+
+                        log::info!(
+                            "{}% completed -> written {readable_current}",
+                            percentage_number as usize
+                        )
+                    }
+                }
+            }
+            Progression::ProgressComplete { name, total } => {
+                let total = total.unwrap_or_default();
+                let readable_total = format_size(total, DECIMAL);
+                let _ = tx.send(FileTransferProgress::Step(FileTransferStep::Upload(
+                    readable_total.clone(),
+                )));
+                log::info!("{name} has been uploaded with {}", readable_total);
+            }
+            Progression::ProgressFailed {
+                name,
+                last_size,
+                error,
+            } => {
+                log::info!(
+                    "{name} failed to upload at {} MB due to: {}",
+                    last_size.unwrap_or_default(),
+                    error.clone().unwrap_or_default()
+                );
+                return Err(Error::OtherWithContext(error.unwrap_or_default()));
+            }
+        }
+    }
+
+    let video_formats = VIDEO_FILE_EXTENSIONS.to_vec();
+    let image_formats = IMAGE_EXTENSIONS.to_vec();
+
+    let file_extension = std::path::Path::new(filename)
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(|s| format!(".{s}"))
+        .unwrap_or_default();
+
+    if image_formats.iter().any(|f| f == &file_extension) {
+        match set_thumbnail_if_file_is_image(warp_storage, filename.to_string()).await {
+            Ok(_) => {
+                log::info!("Image Thumbnail uploaded");
+                let _ = tx.send(FileTransferProgress::Step(FileTransferStep::Thumbnail(
+                    Some(()),
+                )));
+            }
+            Err(error) => {
+                log::error!("Not possible to update thumbnail for image: {:?}", error);
+                let _ = tx.send(FileTransferProgress::Step(FileTransferStep::Thumbnail(
+                    None,
+                )));
+            }
+        };
+    }
+
+    if video_formats.iter().any(|f| f == &file_extension) {
+        match set_thumbnail_if_file_is_video(
+            warp_storage,
+            filename.to_string(),
+            file_path.to_path_buf(),
+        )
+        .await
+        {
+            Ok(_) => {
+                log::info!("Video Thumbnail uploaded");
+                let _ = tx.send(FileTransferProgress::Step(FileTransferStep::Thumbnail(
+                    Some(()),
+                )));
+            }
+            Err(error) => {
+                log::error!("Not possible to update thumbnail for video: {:?}", error);
+                let _ = tx.send(FileTransferProgress::Step(FileTransferStep::Thumbnail(
+                    None,
+                )));
+            }
+        };
+    }
+    Ok(())
 }
 
 fn rename_if_duplicate(
@@ -585,7 +1724,8 @@ fn rename_if_duplicate(
     new_file_name
 }
 
-fn set_thumbnail_if_file_is_video(
+// thumbnails are made off the warp_runner task. the error is turned into a string because it has to be Send
+async fn set_thumbnail_if_file_is_video(
     warp_storage: &warp_storage,
     filename_to_save: String,
     file_path: PathBuf,
@@ -593,51 +1733,12 @@ fn set_thumbnail_if_file_is_video(
     let item = warp_storage
         .current_directory()?
         .get_item(&filename_to_save)?;
-
-    let file_stem = file_path
-        .file_stem()
-        .and_then(OsStr::to_str)
-        .map(str::to_string)
-        .unwrap_or_default();
-
-    let temp_dir = TempDir::new()?;
-
-    let temp_path = temp_dir.path().join(file_stem);
-
-    let output = Command::new("ffmpeg")
-        .args([
-            "-i",
-            &file_path.to_string_lossy(),
-            "-vf",
-            "select=eq(pict_type\\,I)",
-            "-q:v",
-            "2",
-            "-f",
-            "image2",
-            "-update",
-            "1",
-            &temp_path.to_string_lossy(),
-        ])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::null())
-        .spawn()?;
-
-    if let Some(mut child) = output.stdout {
-        let mut contents = vec![];
-
-        child.read_to_end(&mut contents)?;
-
-        let image = std::fs::read(temp_path)?;
-
-        let prefix = format!("data:{};base64,", IMAGE_JPEG);
-        let base64_image = base64::encode(image);
-        let img = prefix + base64_image.as_str();
-        item.set_thumbnail(&img);
-        Ok(())
-    } else {
-        log::warn!("Failed to save thumbnail from a video file");
-        Err(Box::from(Error::InvalidConversion))
-    }
+    let thumbnail = tokio::task::spawn_blocking(move || {
+        thumbnails::video_thumbnail(&file_path).map_err(|e| e.to_string())
+    })
+    .await??;
+    item.set_thumbnail(&thumbnail);
+    Ok(())
 }
 
 async fn set_thumbnail_if_file_is_image(
@@ -648,50 +1749,184 @@ async fn set_thumbnail_if_file_is_image(
         .current_directory()?
         .get_item(&filename_to_save)?;
     let file = warp_storage.get_buffer(&filename_to_save).await?;
+    if file.is_empty() {
+        log::warn!("thumbnail file is empty");
+        return Err(Box::from(Error::InvalidItem));
+    }
 
-    // Since files selected are filtered to be jpg, jpeg, png or svg the last branch is not reachable
     let extension = Path::new(&filename_to_save)
         .extension()
         .and_then(OsStr::to_str)
-        .map(|ext| ext.to_lowercase());
-
-    let mime = match extension {
-        Some(m) => match m.as_str() {
-            "png" => IMAGE_PNG.to_string(),
-            "jpg" => IMAGE_JPEG.to_string(),
-            "jpeg" => IMAGE_JPEG.to_string(),
-            "svg" => IMAGE_SVG.to_string(),
-            _ => {
-                log::warn!("invalid mime type: {m:?}");
-                return Err(Box::from(Error::InvalidItem));
-            }
-        },
-        None => {
-            log::warn!("thumbnail has no mime type");
-            return Err(Box::from(Error::InvalidItem));
-        }
-    };
+        .unwrap_or_default()
+        .to_string();
+    let thumbnail = tokio::task::spawn_blocking(move || {
+        thumbnails::image_thumbnail(&file, &extension).map_err(|e| e.to_string())
+    })
+    .await??;
+    item.set_thumbnail(&thumbnail);
+    Ok(())
+}
 
-    if !file.is_empty() {
-        let prefix = format!("data:{mime};base64,");
-        let base64_image = base64::encode(&file);
-        let img = prefix + base64_image.as_str();
-        item.set_thumbnail(&img);
-        Ok(())
+async fn download_file(
+    warp_storage: &mut warp_storage,
+    path: &str,
+    local_path_to_save_file: PathBuf,
+) -> Result<Uuid, Error> {
+    let (parent, file_name) = split_item_path(path).ok_or(Error::InvalidItem)?;
+    let directory = enter_directory(warp_storage, &parent)?;
+    let mut info = DownloadInfo::new(
+        file_name.clone(),
+        local_path_to_save_file,
+        DownloadSource::Storage,
+    );
+    info.total = directory.get_item(&file_name).ok().map(|item| item.size());
+    let id = info.id;
+
+    if info.is_complete() {
+        log::info!("{file_name} was already downloaded");
+        downloads::report_complete(info);
     } else {
-        log::warn!("thumbnail file is empty");
-        Err(Box::from(Error::InvalidItem))
+        let stream = warp_storage.get_stream(&file_name).await?;
+        downloads::start(info, DownloadJob::Bytes(stream));
     }
+    Ok(id)
 }
 
-async fn download_file(
+async fn download_directory(
+    warp_storage: &mut warp_storage,
+    path: &str,
+    dest: PathBuf,
+) -> Result<Uuid, Error> {
+    let directory = resolve_directory(warp_storage, path)?;
+    let name = split_item_path(path)
+        .map(|(_, name)| name)
+        .unwrap_or_else(|| directory.name());
+    let (job, total) = directory_job(warp_storage, &normalize_path(path), directory, &name)?;
+
+    let mut info = DownloadInfo::new(format!("{name}.zip"), dest, DownloadSource::Storage);
+    // the size of the files. the zip file is smaller
+    info.total = Some(total);
+    let id = info.id;
+    downloads::start(info, job);
+    Ok(id)
+}
+
+// lists every file in the directory and the directories in it. the streams are opened by the download once it
+// leaves the queue. returns the job and the size of the files. the zip paths in the job start with `name`, so the
+// zip file has a single directory at the top
+fn directory_job(
     warp_storage: &warp_storage,
-    file_name: String,
-    local_path_to_save_file: PathBuf,
-) -> Result<(), Error> {
-    warp_storage
-        .get(&file_name, &local_path_to_save_file.to_string_lossy())
-        .await?;
-    log::info!("{file_name} downloaded");
-    Ok(())
+    path: &str,
+    directory: Directory,
+    name: &str,
+) -> Result<(DownloadJob, usize), Error> {
+    let mut directories = vec![];
+    let mut files = vec![];
+    let mut total = 0;
+    // (directory, its path in storage, its path in the zip file)
+    let mut pending = vec![(directory, path.to_string(), PathBuf::from(name))];
+    while let Some((directory, path, zip_path)) = pending.pop() {
+        for item in directory.get_items() {
+            let item_path = join_item_path(&path, &item.name());
+            let item_zip_path = zip_path.join(item.name());
+            if item.is_directory() {
+                pending.push((item.get_directory()?, item_path, item_zip_path));
+            } else {
+                total += item.size();
+                files.push((PathBuf::from(item_path), item_zip_path));
+            }
+        }
+        directories.push(zip_path);
+    }
+    let job = DownloadJob::Directory {
+        storage: warp_storage.clone(),
+        directories,
+        files,
+    };
+    Ok((job, total))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn item_paths_are_split_into_parent_and_name() {
+        assert_eq!(
+            split_item_path("/photos/2020/cat.png"),
+            Some(("/photos/2020".into(), "cat.png".into()))
+        );
+        assert_eq!(
+            split_item_path("/cat.png"),
+            Some(("/".into(), "cat.png".into()))
+        );
+        assert_eq!(split_item_path("/"), None);
+    }
+
+    #[test]
+    fn directory_can_not_be_moved_into_itself() {
+        assert!(is_within("/photos", "/photos"));
+        assert!(is_within("/photos/2020/", "/photos"));
+        assert!(!is_within("/photos-old", "/photos"));
+        assert!(!is_within("/", "/photos"));
+    }
+
+    #[test]
+    fn largest_files_come_first() {
+        let files = vec![
+            ("/a.txt".to_string(), 10),
+            ("/b.png".to_string(), 300),
+            ("/photos/c.png".to_string(), 200),
+        ];
+        assert_eq!(
+            largest(files, 2),
+            vec![
+                ("/b.png".to_string(), 300),
+                ("/photos/c.png".to_string(), 200)
+            ]
+        );
+    }
+
+    #[test]
+    fn search_matches_name_extension_and_mime() {
+        let now = Utc::now();
+        let matches = |query: &str, name: &str| {
+            SearchFilter::new(query, None, None, None).matches(name, 10, now)
+        };
+        assert!(matches("", "notes.txt"));
+        assert!(matches("CAT", "my cat.png"));
+        assert!(matches(".png", "cat.PNG"));
+        assert!(!matches(".png", "png.txt"));
+        assert!(matches("image/*", "cat.webp"));
+        assert!(matches("image/jpeg", "cat.jpg"));
+        assert!(!matches("image/png", "clip.png.mp4"));
+    }
+
+    #[test]
+    fn search_filters_by_kind_size_and_date() {
+        let now = Utc::now();
+        let filter = SearchFilter::new(
+            "",
+            Some(FileCategory::Video),
+            Some(100),
+            Some(now - chrono::Duration::days(1)),
+        );
+        assert!(filter.matches("clip.mp4", 100, now));
+        assert!(!filter.matches("cat.png", 100, now));
+        assert!(!filter.matches("clip.mp4", 99, now));
+        assert!(!filter.matches("clip.mp4", 100, now - chrono::Duration::days(2)));
+    }
+
+    #[test]
+    fn breadcrumbs_follow_the_path() {
+        let dirs = vec![
+            Directory::new("root"),
+            Directory::new("photos"),
+            Directory::new("2020"),
+        ];
+        let crumbs = breadcrumbs(&dirs);
+        let paths: Vec<&str> = crumbs.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, vec!["/", "/photos", "/photos/2020"]);
+        assert_eq!(crumbs[2].name, "2020");
+    }
 }
@@ -7,6 +7,7 @@ mod tesseract_commands;
 // this shortens the path required to use the functions and structs
 pub use constellation_commands::{
     handle_constellation_cmd, ConstellationCmd, FileTransferProgress, FileTransferStep,
+    FolderUploadSummary, DOCUMENT_EXTENSIONS, IMAGE_EXTENSIONS, VIDEO_FILE_EXTENSIONS,
 };
 pub use multipass_commands::{handle_multipass_cmd, MultiPassCmd};
 pub use other_commands::*;
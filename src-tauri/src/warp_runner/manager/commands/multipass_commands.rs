@@ -1,4 +1,8 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    str::FromStr,
+};
 
 use derive_more::Display;
 use futures::channel::oneshot;
@@ -8,21 +12,38 @@ use warp::{
     logging::tracing::log,
     multipass::{
         self,
-        identity::{self, IdentityUpdate},
+        identity::{self, Identifier, IdentityStatus, IdentityUpdate},
     },
 };
 
 use crate::{
     state::{self, friends},
-    warp_runner::{ui_adapter::dids_to_identity, Account},
+    warp_runner::{
+        account_deletion::DeletionReport, login_throttle, ui_adapter::dids_to_identity, Account,
+    },
 };
 
 #[derive(Display)]
 pub enum MultiPassCmd {
+    // the response includes the recovery phrase for the new keypair. it is only available here
     #[display(fmt = "CreateIdentity {{ username: {username} }} ")]
     CreateIdentity {
         username: String,
         passphrase: String,
+        rsp: oneshot::Sender<Result<(multipass::identity::Identity, String), warp::error::Error>>,
+    },
+    // closes warp and deletes all of the account's data. see account_deletion
+    #[display(fmt = "DeleteAccount {{ backup_dir: {backup_dir:?} }} ")]
+    DeleteAccount {
+        passphrase: String,
+        backup_dir: Option<PathBuf>,
+        rsp: oneshot::Sender<Result<DeletionReport, warp::error::Error>>,
+    },
+    // rebuilds the keypair from the recovery phrase. replaces any existing account
+    #[display(fmt = "RecoverIdentity")]
+    RecoverIdentity {
+        mnemonic: String,
+        new_passphrase: String,
         rsp: oneshot::Sender<Result<multipass::identity::Identity, warp::error::Error>>,
     },
     #[display(fmt = "TryLogIn")]
@@ -103,6 +124,18 @@ pub enum MultiPassCmd {
         username: String,
         rsp: oneshot::Sender<Result<identity::Identity, warp::error::Error>>,
     },
+    // publishes the user's own status (online, away, busy, or offline)
+    #[display(fmt = "SetIdentityStatus {{ status: {status:?} }} ")]
+    SetIdentityStatus {
+        status: IdentityStatus,
+        rsp: oneshot::Sender<Result<(), warp::error::Error>>,
+    },
+    // query is a username, username#short_id, or did:key. returns every identity which matches
+    #[display(fmt = "LookupIdentity {{ query: {query} }} ")]
+    LookupIdentity {
+        query: String,
+        rsp: oneshot::Sender<Result<Vec<state::Identity>, warp::error::Error>>,
+    },
 }
 
 // hide sensitive information from debug logs
@@ -115,9 +148,20 @@ impl std::fmt::Debug for MultiPassCmd {
 
 pub async fn handle_multipass_cmd(cmd: MultiPassCmd, warp: &mut super::super::Warp) {
     match cmd {
-        MultiPassCmd::CreateIdentity { .. } | MultiPassCmd::TryLogIn { .. } => {
+        // DeleteAccount is handled by manager::run, which needs to close warp first
+        MultiPassCmd::CreateIdentity { .. }
+        | MultiPassCmd::RecoverIdentity { .. }
+        | MultiPassCmd::DeleteAccount { .. } => {
             // do nothing and drop the rsp channel
         }
+        // only happens after auto-lock. the first login is handled by warp_runner::handle_login
+        MultiPassCmd::TryLogIn { passphrase, rsp } => {
+            let r = match login_throttle::unlock(&warp.tesseract, &passphrase) {
+                Ok(_) => warp.multipass.get_own_identity().await,
+                Err(e) => Err(e),
+            };
+            let _ = rsp.send(r);
+        }
         MultiPassCmd::RequestFriend { did, rsp } => {
             let r = warp.multipass.send_request(&did).await;
             let _ = rsp.send(r);
@@ -233,7 +277,79 @@ pub async fn handle_multipass_cmd(cmd: MultiPassCmd, warp: &mut super::super::Wa
                 }
             };
         }
+        MultiPassCmd::SetIdentityStatus { status, rsp } => {
+            let r = warp.multipass.set_identity_status(status).await;
+            if let Err(e) = &r {
+                log::error!("failed to set identity status: {e}");
+            }
+            let _ = rsp.send(r);
+        }
+        MultiPassCmd::LookupIdentity { query, rsp } => {
+            let r = multipass_lookup_identity(&query, &warp.multipass).await;
+            let _ = rsp.send(r);
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum LookupQuery {
+    Did(DID),
+    Username {
+        username: String,
+        short_id: Option<String>,
+    },
+}
+
+fn parse_lookup_query(query: &str) -> Option<LookupQuery> {
+    let query = query.trim();
+    if query.starts_with("did:key:") {
+        return DID::from_str(query).ok().map(LookupQuery::Did);
     }
+    let (username, short_id) = match query.rsplit_once('#') {
+        Some((username, short_id)) => (username.trim(), Some(short_id.trim())),
+        None => (query, None),
+    };
+    if username.is_empty() {
+        return None;
+    }
+    Some(LookupQuery::Username {
+        username: username.to_string(),
+        short_id: short_id
+            .filter(|short_id| !short_id.is_empty())
+            .map(|short_id| short_id.to_string()),
+    })
+}
+
+async fn multipass_lookup_identity(
+    query: &str,
+    account: &Account,
+) -> Result<Vec<state::Identity>, Error> {
+    let (identifier, short_id) = match parse_lookup_query(query) {
+        Some(LookupQuery::Did(did)) => (Identifier::DID(did), None),
+        Some(LookupQuery::Username { username, short_id }) => {
+            (Identifier::Username(username), short_id)
+        }
+        None => return Ok(vec![]),
+    };
+
+    let own_did = account.get_own_identity().await?.did_key();
+    let dids: Vec<DID> = account
+        .get_identity(identifier)
+        .await?
+        .iter()
+        .filter(|identity| identity.did_key() != own_did)
+        .filter(|identity| match &short_id {
+            Some(short_id) => identity
+                .short_id()
+                .to_string()
+                .eq_ignore_ascii_case(short_id),
+            None => true,
+        })
+        .map(|identity| identity.did_key())
+        .collect();
+
+    // the avatar is part of the identity's graphics
+    dids_to_identity(&dids, account).await
 }
 
 async fn multipass_refresh_friends(
@@ -283,3 +399,37 @@ async fn multipass_initialize_friends(
     };
     Ok((ret, ids))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_username_with_short_id() {
+        assert_eq!(
+            parse_lookup_query(" alice#a1b2 "),
+            Some(LookupQuery::Username {
+                username: "alice".into(),
+                short_id: Some("a1b2".into()),
+            })
+        );
+        assert_eq!(
+            parse_lookup_query("alice#"),
+            Some(LookupQuery::Username {
+                username: "alice".into(),
+                short_id: None,
+            })
+        );
+        assert_eq!(parse_lookup_query("#a1b2"), None);
+    }
+
+    #[test]
+    fn parse_did() {
+        let did = DID::default();
+        assert_eq!(
+            parse_lookup_query(&did.to_string()),
+            Some(LookupQuery::Did(did))
+        );
+        assert_eq!(parse_lookup_query("did:key:invalid"), None);
+    }
+}
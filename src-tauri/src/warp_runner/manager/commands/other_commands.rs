@@ -4,11 +4,21 @@ use std::{
     path::{Path, PathBuf},
 };
 use walkdir::WalkDir;
-use zip::{result::ZipError, write::FileOptions};
+use zip::{result::ZipError, write::FileOptions, ZipArchive};
 
 use derive_more::Display;
 use futures::channel::oneshot;
 use warp::{error, logging::tracing::log};
+
+use crate::{
+    state::chats::ArchivedChat,
+    warp_runner::{
+        archive::{self, ConversationArchive},
+        ui_adapter,
+    },
+    STATIC_ARGS,
+};
+
 #[derive(Display, Debug)]
 pub enum OtherCmd {
     #[display(fmt = "CompressFolder {{ src: {src:?}, dest: {dest:?} }} ")]
@@ -17,6 +27,14 @@ pub enum OtherCmd {
         dest: PathBuf,
         rsp: oneshot::Sender<Result<(), error::Error>>,
     },
+    // `archive` is either the json file written by RayGunCmd::ExportConversation or the zip file which bundles it
+    // with the attachments. `attachments` is only needed if the json file and the attachments are separate.
+    #[display(fmt = "ImportArchive {{ archive: {archive:?}, attachments: {attachments:?} }} ")]
+    ImportArchive {
+        archive: PathBuf,
+        attachments: Option<PathBuf>,
+        rsp: oneshot::Sender<Result<ArchivedChat, error::Error>>,
+    },
 }
 
 pub async fn handle_other_cmd(cmd: OtherCmd) {
@@ -25,10 +43,86 @@ pub async fn handle_other_cmd(cmd: OtherCmd) {
             let r = compress_folder(src, dest).await;
             let _ = rsp.send(r);
         }
+        OtherCmd::ImportArchive {
+            archive,
+            attachments,
+            rsp,
+        } => {
+            let r = match tokio::task::spawn_blocking(move || {
+                import_archive(&archive, attachments.as_deref())
+            })
+            .await
+            {
+                Ok(r) => r,
+                Err(e) => Err(error::Error::OtherWithContext(e.to_string())),
+            };
+            let _ = rsp.send(r);
+        }
     }
 }
 
-async fn compress_folder(src: PathBuf, dest: PathBuf) -> Result<(), error::Error> {
+// copies the archive to STATIC_ARGS.archives_path so it stays available if the original files are moved
+fn import_archive(
+    archive_path: &Path,
+    attachments_path: Option<&Path>,
+) -> Result<ArchivedChat, error::Error> {
+    let to_err = |e: ZipError| error::Error::OtherWithContext(e.to_string());
+    let is_zip = archive_path.extension().map(|ext| ext == "zip") == Some(true);
+
+    let archive = if is_zip {
+        let mut zip = ZipArchive::new(File::open(archive_path)?).map_err(to_err)?;
+        read_archive_from_zip(&mut zip)?
+    } else {
+        archive::parse(&std::fs::read(archive_path)?)?
+    };
+
+    let dest = STATIC_ARGS
+        .archives_path
+        .join(archive.conversation_id.to_string());
+    // importing the same conversation again replaces the old copy
+    if dest.exists() {
+        std::fs::remove_dir_all(&dest)?;
+    }
+    std::fs::create_dir_all(&dest)?;
+
+    if is_zip {
+        ZipArchive::new(File::open(archive_path)?)
+            .and_then(|mut zip| zip.extract(&dest))
+            .map_err(to_err)?;
+    } else {
+        std::fs::copy(archive_path, dest.join("archive.json"))?;
+    }
+    if let Some(path) = attachments_path {
+        ZipArchive::new(File::open(path)?)
+            .and_then(|mut zip| zip.extract(&dest))
+            .map_err(to_err)?;
+    }
+
+    log::info!(
+        "imported archive of conversation {} with {} messages",
+        archive.conversation_id,
+        archive.messages.len()
+    );
+    Ok(ui_adapter::archive_to_chat(&archive, dest))
+}
+
+// the export is the only json file in the top level of the zip file
+fn read_archive_from_zip<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+) -> Result<ConversationArchive, error::Error> {
+    let name = zip
+        .file_names()
+        .find(|name| name.ends_with(".json") && !name.contains('/'))
+        .map(|name| name.to_string())
+        .ok_or_else(|| error::Error::OtherWithContext("archive not found in zip file".into()))?;
+    let mut contents = vec![];
+    zip.by_name(&name)
+        .map_err(|e| error::Error::OtherWithContext(e.to_string()))?
+        .read_to_end(&mut contents)?;
+    archive::parse(&contents)
+}
+
+pub async fn compress_folder(src: PathBuf, dest: PathBuf) -> Result<(), error::Error> {
     // I know that warp_runner is basically single threaded but still...put the blocking operation in a separate task and await it
     let handle = tokio::task::spawn_blocking(move || {
         let z = || -> Result<(), ZipError> {
@@ -102,3 +196,44 @@ where
     zip.finish()?;
     Result::Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+
+    const ARCHIVE: &str = r#"{
+        "version": 1,
+        "exported_at": "2023-03-01T12:00:00Z",
+        "conversation_id": "00000000-0000-0000-0000-000000000000",
+        "participants": [],
+        "messages": []
+    }"#;
+
+    fn zip_with(files: &[(&str, &str)]) -> ZipArchive<Cursor<Vec<u8>>> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in files {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        let cursor = zip.finish().unwrap();
+        ZipArchive::new(cursor).unwrap()
+    }
+
+    #[test]
+    fn reads_archive_from_zip() {
+        let mut zip = zip_with(&[
+            ("attachments/1/notes.json", "{}"),
+            ("conversation.json", ARCHIVE),
+        ]);
+        let archive = read_archive_from_zip(&mut zip).unwrap();
+        assert_eq!(archive.version, 1);
+        assert!(archive.messages.is_empty());
+    }
+
+    #[test]
+    fn rejects_newer_archive_version() {
+        let newer = ARCHIVE.replace("\"version\": 1", "\"version\": 999");
+        assert!(archive::parse(newer.as_bytes()).is_err());
+    }
+}
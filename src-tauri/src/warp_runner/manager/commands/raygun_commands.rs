@@ -1,27 +1,41 @@
+use chrono::Utc;
 use derive_more::Display;
-use futures::channel::oneshot;
+use futures::{channel::oneshot, stream, StreamExt};
 use std::{
     collections::{HashMap, HashSet},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
+use tempfile::TempDir;
 use uuid::Uuid;
 use warp::{
-    constellation::ConstellationProgressStream,
+    constellation::Progression,
     crypto::DID,
     error::Error,
     logging::tracing::log,
-    raygun::{self, ConversationType, ReactionState},
+    raygun::{self, ConversationType, MessageOptions, ReactionState},
 };
 
 use crate::{
     state::{self, chats},
     warp_runner::{
+        archive::{
+            self, ArchivedAttachment, ArchivedMessage, ArchivedParticipant, ArchivedReaction,
+            ConversationArchive, ExportFormat, ExportRange,
+        },
         conv_stream,
-        ui_adapter::{conversation_to_chat, ChatAdapter},
+        downloads::{self, DownloadInfo, DownloadJob, DownloadSource},
+        ui_adapter::{conversation_to_chat, did_to_identity, ChatAdapter},
         Account, Messaging,
     },
 };
 
+use super::other_commands::compress_folder;
+
+// how many messages are fetched at a time when exporting a conversation
+const EXPORT_PAGE_SIZE: usize = 100;
+// how many conversations are loaded at once when logging in
+const MAX_CONCURRENT_CONVERSATIONS: usize = 8;
+
 #[allow(clippy::large_enum_variant)]
 #[derive(Display)]
 pub enum RayGunCmd {
@@ -53,13 +67,14 @@ pub enum RayGunCmd {
         msg: Vec<String>,
         rsp: oneshot::Sender<Result<(), warp::error::Error>>,
     },
+    // queues the download and returns its id. progress is reported via WarpEvent::Download
     #[display(fmt = "DownloadAttachment")]
     DownloadAttachment {
         conv_id: Uuid,
         msg_id: Uuid,
         file_name: String,
         directory: PathBuf,
-        rsp: oneshot::Sender<Result<ConstellationProgressStream, warp::error::Error>>,
+        rsp: oneshot::Sender<Result<Uuid, warp::error::Error>>,
     },
     #[display(fmt = "DeleteMessage {{ conv_id: {conv_id}, msg_id: {msg_id} }} ")]
     DeleteMessage {
@@ -80,6 +95,11 @@ pub enum RayGunCmd {
         recipient: DID,
         rsp: oneshot::Sender<Result<(), warp::error::Error>>,
     },
+    #[display(fmt = "DeleteConversation {{ conv_id: {conv_id} }} ")]
+    DeleteConversation {
+        conv_id: Uuid,
+        rsp: oneshot::Sender<Result<(), warp::error::Error>>,
+    },
     #[display(fmt = "React {{ conversation_id: {conversation_id} }} ")]
     React {
         conversation_id: Uuid,
@@ -94,6 +114,19 @@ pub enum RayGunCmd {
         event: raygun::MessageEvent,
         rsp: oneshot::Sender<Result<(), warp::error::Error>>,
     },
+    // writes the conversation to a file in `directory` and returns its path. if attachments are included,
+    // the file and the attachments are bundled in a zip file instead.
+    #[display(
+        fmt = "ExportConversation {{ conv_id: {conv_id}, format: {format}, include_attachments: {include_attachments} }} "
+    )]
+    ExportConversation {
+        conv_id: Uuid,
+        format: ExportFormat,
+        range: ExportRange,
+        include_attachments: bool,
+        directory: PathBuf,
+        rsp: oneshot::Sender<Result<PathBuf, warp::error::Error>>,
+    },
 }
 
 impl std::fmt::Debug for RayGunCmd {
@@ -160,8 +193,8 @@ pub async fn handle_raygun_cmd(
             directory,
             rsp,
         } => {
-            let pb = directory.join(&file_name);
-            let r = messaging.download(conv_id, msg_id, file_name, pb).await;
+            let r =
+                raygun_download_attachment(conv_id, msg_id, file_name, directory, messaging).await;
             let _ = rsp.send(r);
         }
         RayGunCmd::DeleteMessage {
@@ -185,6 +218,10 @@ pub async fn handle_raygun_cmd(
             let r = raygun_remove_direct_convs(recipient, messaging).await;
             let _ = rsp.send(r);
         }
+        RayGunCmd::DeleteConversation { conv_id, rsp } => {
+            let r = messaging.delete(conv_id, None).await;
+            let _ = rsp.send(r);
+        }
         RayGunCmd::React {
             conversation_id,
             message_id,
@@ -205,6 +242,26 @@ pub async fn handle_raygun_cmd(
             let r = messaging.send_event(conv_id, event).await;
             let _ = rsp.send(r);
         }
+        RayGunCmd::ExportConversation {
+            conv_id,
+            format,
+            range,
+            include_attachments,
+            directory,
+            rsp,
+        } => {
+            let r = raygun_export_conversation(
+                conv_id,
+                format,
+                range,
+                include_attachments,
+                directory,
+                account,
+                messaging,
+            )
+            .await;
+            let _ = rsp.send(r);
+        }
     }
 }
 
@@ -215,10 +272,17 @@ async fn raygun_initialize_conversations(
     messaging: &mut Messaging,
 ) -> Result<(HashMap<Uuid, chats::Chat>, HashSet<state::Identity>), Error> {
     log::trace!("init convs with {} total", convs.len());
+    let messaging_ref: &Messaging = messaging;
+    let converted: Vec<Result<ChatAdapter, Error>> = stream::iter(convs)
+        .map(|conv| conversation_to_chat(conv, account, messaging_ref))
+        .buffer_unordered(MAX_CONCURRENT_CONVERSATIONS)
+        .collect()
+        .await;
+
     let mut all_chats = HashMap::new();
     let mut identities = HashSet::new();
-    for conv in convs {
-        match conversation_to_chat(conv, account, messaging).await {
+    for chat in converted {
+        match chat {
             Ok(chat) => {
                 if let Err(e) = stream_manager.add_stream(chat.inner.id, messaging).await {
                     log::error!(
@@ -238,6 +302,199 @@ async fn raygun_initialize_conversations(
     Ok((all_chats, identities))
 }
 
+async fn raygun_download_attachment(
+    conv_id: Uuid,
+    msg_id: Uuid,
+    file_name: String,
+    directory: PathBuf,
+    messaging: &mut Messaging,
+) -> Result<Uuid, Error> {
+    // the name is chosen by the sender. it is still used as is to ask RayGun for the file
+    let local_name = archive::attachment_file_name(&file_name)
+        .ok_or_else(|| Error::OtherWithContext(format!("invalid attachment name: {file_name}")))?;
+    let mut info = DownloadInfo::new(
+        file_name.clone(),
+        directory.join(local_name),
+        DownloadSource::Attachment {
+            conversation_id: conv_id,
+            message_id: msg_id,
+        },
+    );
+    info.total = messaging
+        .get_message(conv_id, msg_id)
+        .await?
+        .attachments()
+        .iter()
+        .find(|file| file.name() == file_name)
+        .map(|file| file.size());
+    let id = info.id;
+
+    if info.is_complete() {
+        log::info!("{file_name} was already downloaded");
+        downloads::report_complete(info);
+    } else {
+        downloads::start(
+            info,
+            DownloadJob::Attachment {
+                conversation_id: conv_id,
+                message_id: msg_id,
+                messaging: messaging.clone(),
+            },
+        );
+    }
+    Ok(id)
+}
+
+async fn raygun_export_conversation(
+    conv_id: Uuid,
+    format: ExportFormat,
+    range: ExportRange,
+    include_attachments: bool,
+    directory: PathBuf,
+    account: &Account,
+    messaging: &mut Messaging,
+) -> Result<PathBuf, Error> {
+    let conv = messaging.get_conversation(conv_id).await?;
+
+    // resolve each sender once, not once per message
+    let mut names: HashMap<DID, String> = HashMap::new();
+    for did in conv.recipients() {
+        let name = did_to_identity(&did, account).await?.username();
+        names.insert(did, name);
+    }
+
+    let temp_dir = TempDir::new()?;
+    let total = messaging.get_message_count(conv_id).await?;
+    let mut messages = vec![];
+    let mut start = 0;
+    while start < total {
+        let end = usize::min(start + EXPORT_PAGE_SIZE, total);
+        let page = messaging
+            .get_messages(conv_id, MessageOptions::default().set_range(start..end))
+            .await?;
+        start = end;
+
+        for msg in page {
+            if let Some(range) = &range {
+                if !range.contains(&msg.date()) {
+                    continue;
+                }
+            }
+
+            let sender = msg.sender();
+            if !names.contains_key(&sender) {
+                let name = did_to_identity(&sender, account).await?.username();
+                names.insert(sender.clone(), name);
+            }
+
+            let mut attachments = vec![];
+            for file in msg.attachments() {
+                let file_name = archive::attachment_file_name(&file.name());
+                if include_attachments && file_name.is_none() {
+                    log::warn!("not bundling attachment with invalid name: {}", file.name());
+                }
+                let path = if let Some(file_name) = file_name.filter(|_| include_attachments) {
+                    let relative = Path::new(archive::ATTACHMENTS_DIR)
+                        .join(msg.id().to_string())
+                        .join(file_name);
+                    let full_path = temp_dir.path().join(&relative);
+                    if let Some(parent) = full_path.parent() {
+                        tokio::fs::create_dir_all(parent).await?;
+                    }
+                    download_to(conv_id, msg.id(), file.name(), full_path, messaging).await?;
+                    Some(relative.to_string_lossy().to_string())
+                } else {
+                    None
+                };
+                attachments.push(ArchivedAttachment {
+                    name: file.name(),
+                    size: file.size(),
+                    path,
+                });
+            }
+
+            messages.push(ArchivedMessage {
+                id: msg.id(),
+                sender: sender.to_string(),
+                sender_name: names.get(&sender).cloned().unwrap_or_default(),
+                date: msg.date(),
+                in_reply_to: msg.replied(),
+                lines: msg.value(),
+                reactions: msg
+                    .reactions()
+                    .iter()
+                    .map(|r| ArchivedReaction {
+                        emoji: r.emoji(),
+                        count: r.users().len(),
+                    })
+                    .collect(),
+                attachments,
+            });
+        }
+    }
+
+    let exported_at = Utc::now();
+    let archive = ConversationArchive {
+        version: archive::ARCHIVE_VERSION,
+        exported_at,
+        conversation_id: conv_id,
+        participants: conv
+            .recipients()
+            .iter()
+            .map(|did| ArchivedParticipant {
+                did: did.to_string(),
+                username: names.get(did).cloned().unwrap_or_default(),
+            })
+            .collect(),
+        messages,
+    };
+    let contents = archive
+        .render(format)
+        .map_err(|e| Error::OtherWithContext(e.to_string()))?;
+
+    let file_stem = format!(
+        "conversation_{}_{}",
+        conv_id.simple(),
+        exported_at.format("%Y%m%d_%H%M%S")
+    );
+    let file_name = format!("{file_stem}.{}", format.extension());
+    tokio::fs::create_dir_all(&directory).await?;
+    if !include_attachments {
+        let dest = directory.join(file_name);
+        tokio::fs::write(&dest, contents).await?;
+        return Ok(dest);
+    }
+
+    tokio::fs::write(temp_dir.path().join(file_name), contents).await?;
+    let dest = directory.join(format!("{file_stem}.zip"));
+    compress_folder(temp_dir.path().to_path_buf(), dest.clone()).await?;
+    Ok(dest)
+}
+
+// downloads an attachment and waits for it to finish
+async fn download_to(
+    conv_id: Uuid,
+    msg_id: Uuid,
+    file_name: String,
+    path: PathBuf,
+    messaging: &mut Messaging,
+) -> Result<(), Error> {
+    let mut stream = messaging.download(conv_id, msg_id, file_name, path).await?;
+    while let Some(progress) = stream.next().await {
+        match progress {
+            Progression::ProgressComplete { .. } => break,
+            Progression::ProgressFailed { name, error, .. } => {
+                return Err(Error::OtherWithContext(format!(
+                    "failed to download {name}: {}",
+                    error.unwrap_or_default()
+                )));
+            }
+            Progression::CurrentProgress { .. } => {}
+        }
+    }
+    Ok(())
+}
+
 async fn raygun_remove_direct_convs(
     recipient: DID,
     messaging: &mut Messaging,
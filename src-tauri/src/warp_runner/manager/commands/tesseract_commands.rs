@@ -5,6 +5,9 @@ use futures::channel::oneshot;
 pub enum TesseractCmd {
     #[display(fmt = "AccountExists")]
     AccountExists { rsp: oneshot::Sender<bool> },
+    // used by auto-lock. MultiPassCmd::TryLogIn unlocks tesseract again
+    #[display(fmt = "Lock")]
+    Lock { rsp: oneshot::Sender<()> },
 }
 
 impl std::fmt::Debug for TesseractCmd {
@@ -6,8 +6,8 @@ use warp::{
 
 use crate::{
     warp_runner::{
-        conv_stream,
-        ui_adapter::{self, did_to_identity, MultiPassEvent},
+        conv_stream, link_preview, safety_number,
+        ui_adapter::{self, did_to_identity, MessageEvent, MultiPassEvent},
         WarpCmd, WarpEvent,
     },
     WARP_EVENT_CH,
@@ -17,7 +17,7 @@ use super::{
     commands::{
         handle_constellation_cmd, handle_multipass_cmd, handle_other_cmd, handle_raygun_cmd,
     },
-    MultiPassCmd,
+    MultiPassCmd, TesseractCmd,
 };
 
 pub async fn handle_multipass_event(
@@ -32,10 +32,29 @@ pub async fn handle_multipass_event(
     let warp_event_tx = WARP_EVENT_CH.tx.clone();
     match ui_adapter::convert_multipass_event(evt, &mut warp.multipass, &mut warp.raygun).await {
         Ok(evt) => {
+            let claims_contact = matches!(
+                evt,
+                MultiPassEvent::FriendRequestReceived(_) | MultiPassEvent::FriendAdded(_)
+            );
+            let key_changed = evt.identity().and_then(|identity| {
+                safety_number::changed_verified_key(identity, claims_contact).map(|previous| {
+                    MultiPassEvent::VerifiedKeyChanged {
+                        previous,
+                        identity: identity.clone(),
+                    }
+                })
+            });
             if warp_event_tx.send(WarpEvent::MultiPass(evt)).is_err() {
                 log::error!("failed to send warp_event");
                 return Err(());
             }
+            if let Some(evt) = key_changed {
+                log::warn!("a verified contact is using a different key");
+                if warp_event_tx.send(WarpEvent::MultiPass(evt)).is_err() {
+                    log::error!("failed to send warp_event");
+                    return Err(());
+                }
+            }
         }
         Err(e) => {
             log::error!("failed to convert multipass event: {}", e);
@@ -90,10 +109,28 @@ pub async fn handle_message_event(
     let warp_event_tx = WARP_EVENT_CH.tx.clone();
     match ui_adapter::convert_message_event(msg, &mut warp.multipass, &mut warp.raygun).await {
         Ok(evt) => {
+            let needs_previews = match &evt {
+                MessageEvent::Received {
+                    conversation_id,
+                    message,
+                }
+                | MessageEvent::Sent {
+                    conversation_id,
+                    message,
+                }
+                | MessageEvent::Edited {
+                    conversation_id,
+                    message,
+                } => Some((*conversation_id, message.inner.id(), message.inner.value())),
+                _ => None,
+            };
             if warp_event_tx.send(WarpEvent::Message(evt)).is_err() {
                 log::error!("failed to send warp_event");
                 return Err(());
             }
+            if let Some((conversation_id, message_id, lines)) = needs_previews {
+                link_preview::spawn_fetch(conversation_id, message_id, lines);
+            }
         }
         Err(e) => {
             log::error!("failed to convert message event: {}", e);
@@ -119,6 +156,10 @@ pub async fn handle_warp_command(
             // this one could be parallelized
             handle_other_cmd(cmd).await;
         }
+        WarpCmd::Tesseract(TesseractCmd::Lock { rsp }) => {
+            warp.tesseract.lock();
+            let _ = rsp.send(());
+        }
         WarpCmd::Tesseract(_cmd) => {
             // not accepted at this stage of the program. do nothing and drop the rsp channel
         }
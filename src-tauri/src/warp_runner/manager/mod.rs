@@ -10,7 +10,7 @@ use warp::{
     tesseract::Tesseract,
 };
 
-use super::{conv_stream, Account, Messaging, Storage};
+use super::{account_deletion, conv_stream, Account, Messaging, Storage, WarpCmd};
 use crate::WARP_CMD_CH;
 
 pub use commands::{ConstellationCmd, MultiPassCmd, OtherCmd, RayGunCmd, TesseractCmd};
@@ -23,7 +23,8 @@ pub struct Warp {
     pub constellation: Storage,
 }
 
-pub async fn run(mut warp: Warp, notify: Arc<Notify>) {
+/// returns true if the account was deleted. warp_runner then starts over so that a new account can be created
+pub async fn run(mut warp: Warp, notify: Arc<Notify>) -> bool {
     // receive command from Uplink
     let warp_cmd_rx = WARP_CMD_CH.rx.clone();
 
@@ -39,6 +40,7 @@ pub async fn run(mut warp: Warp, notify: Arc<Notify>) {
     let mut multipass_stream = get_multipass_stream(&mut warp.multipass).await;
 
     log::debug!("warp_runner::manager::run");
+    let mut deletion = None;
     loop {
         tokio::select! {
             opt = multipass_stream.next() => {
@@ -57,6 +59,31 @@ pub async fn run(mut warp: Warp, notify: Arc<Notify>) {
                 }
             }
             opt = warp_cmd_rx.recv() => {
+                // warp has to be closed before its files can be deleted, which happens once this loop ends
+                if let Some(WarpCmd::MultiPass(MultiPassCmd::DeleteAccount {
+                    passphrase,
+                    backup_dir,
+                    rsp,
+                })) = opt
+                {
+                    if let Err(e) = account_deletion::verify_passphrase(&passphrase) {
+                        let _ = rsp.send(Err(e));
+                        continue;
+                    }
+                    // if the backup fails nothing is deleted and warp keeps running
+                    let backup = match backup_dir {
+                        Some(dir) => match account_deletion::backup_account(&passphrase, &dir).await {
+                            Ok(path) => Some(path),
+                            Err(e) => {
+                                let _ = rsp.send(Err(e));
+                                continue;
+                            }
+                        },
+                        None => None,
+                    };
+                    deletion = Some((backup, rsp));
+                    break;
+                }
                 if events::handle_warp_command(opt, &mut warp, &mut conversation_manager).await.is_err() {
                     break;
                 }
@@ -67,6 +94,18 @@ pub async fn run(mut warp: Warp, notify: Arc<Notify>) {
     }
 
     log::debug!("terminating warp_runner thread");
+
+    match deletion {
+        Some((backup, rsp)) => {
+            drop(raygun_stream);
+            drop(multipass_stream);
+            drop(conversation_manager);
+            drop(warp);
+            let _ = rsp.send(Ok(account_deletion::delete_account(backup)));
+            true
+        }
+        None => false,
+    }
 }
 
 async fn get_raygun_stream(rg: &mut Messaging) -> RayGunEventStream {
@@ -1,4 +1,5 @@
 //! Defines important types and structs, and spawns the main task for warp_runner - manager::run.
+use chrono::Utc;
 use derive_more::Display;
 use std::sync::Arc;
 use tokio::sync::{
@@ -7,6 +8,7 @@ use tokio::sync::{
 };
 use warp::{
     constellation::Constellation,
+    crypto::keypair::{did_from_mnemonic, generate_keypair, PhraseType},
     error::Error,
     logging::tracing::log,
     multipass::{self, MultiPass},
@@ -21,11 +23,24 @@ use crate::{STATIC_ARGS, WARP_CMD_CH};
 
 use self::ui_adapter::{MultiPassEvent, RayGunEvent};
 
+pub mod account_deletion;
+pub mod archive;
+pub mod content_index;
 mod conv_stream;
+pub mod downloads;
+pub mod link_preview;
+pub mod login_throttle;
 mod manager;
+pub mod passphrase;
+pub mod safety_number;
+pub mod thumbnails;
+pub mod trash;
 pub mod ui_adapter;
 
-pub use manager::commands::{FileTransferProgress, FileTransferStep};
+pub use manager::commands::{
+    FileTransferProgress, FileTransferStep, FolderUploadSummary, DOCUMENT_EXTENSIONS,
+    IMAGE_EXTENSIONS, VIDEO_FILE_EXTENSIONS,
+};
 pub use manager::{ConstellationCmd, MultiPassCmd, OtherCmd, RayGunCmd, TesseractCmd};
 
 pub type WarpCmdTx = UnboundedSender<WarpCmd>;
@@ -52,6 +67,7 @@ pub enum WarpEvent {
     RayGun(RayGunEvent),
     Message(ui_adapter::MessageEvent),
     MultiPass(MultiPassEvent),
+    Download(downloads::DownloadEvent),
 }
 
 #[derive(Display, Debug)]
@@ -104,13 +120,15 @@ impl WarpRunner {
 
         let notify = self.notify.clone();
         tokio::spawn(async move {
-            handle_login(notify.clone()).await;
+            // starts over after the account is deleted, so that a new one can be created
+            while handle_login(notify.clone()).await {}
         });
     }
 }
 
 // handle_login calls manager::run, which continues to process warp commands
-async fn handle_login(notify: Arc<Notify>) {
+// returns true if the account was deleted
+async fn handle_login(notify: Arc<Notify>) -> bool {
     let warp_cmd_rx = WARP_CMD_CH.rx.clone();
     // be sure to drop this channel before calling manager::run()
     let mut warp_cmd_rx = warp_cmd_rx.lock().await;
@@ -123,11 +141,12 @@ async fn handle_login(notify: Arc<Notify>) {
         Ok(w) => w,
         Err(e) => {
             log::error!("warp init failed: {}", e);
-            return;
+            return false;
         }
     };
 
     let account_exists = warp.tesseract.exist("keypair");
+    let mut login_attempts = login_throttle::LoginAttempts::load();
 
     // until the user logs in, raygun and multipass are no use.
     let warp: Option<manager::Warp> = loop {
@@ -143,6 +162,13 @@ async fn handle_login(notify: Arc<Notify>) {
                         passphrase,
                         rsp,
                     })) => {
+                        let policy = passphrase::PassphrasePolicy::load();
+                        let strength = passphrase::check(&passphrase, &policy);
+                        if !strength.acceptable {
+                            let feedback = strength.feedback.join(", ");
+                            let _ = rsp.send(Err(Error::OtherWithContext(feedback)));
+                            continue;
+                        }
                         if account_exists {
                             log::debug!("attempting to overwrite old account");
                             let tesseract = init_tesseract(true)
@@ -152,7 +178,7 @@ async fn handle_login(notify: Arc<Notify>) {
                                 Ok(w) => w,
                                 Err(e) => {
                                     log::error!("warp init failed: {}", e);
-                                    return;
+                                    return false;
                                 }
                             };
                         }
@@ -162,20 +188,22 @@ async fn handle_login(notify: Arc<Notify>) {
                             let _ = rsp.send(Err(e));
                             continue;
                         };
-                        match warp.multipass.create_identity(Some(&username), None).await {
-                            Ok(_id) =>  match wait_for_multipass(&mut warp, notify.clone()).await {
-                                Ok(ident) => match save_tesseract(&warp.tesseract) {
-                                    Ok(_) => {
-                                        let _ = rsp.send(Ok(ident));
-                                        break Some(warp);
-                                    }
-                                    Err(e) => {
-                                        let _ = rsp.send(Err(e));
-                                        continue;
-                                    }
-                                },
+                        // the keypair is derived from this phrase, so it can be used to recover the account
+                        let phrase = match generate_keypair(PhraseType::Standard, None) {
+                            Ok((phrase, _)) => phrase,
+                            Err(e) => {
+                                log::error!("failed to generate recovery phrase: {}", e);
+                                let _ = rsp.send(Err(e));
+                                continue;
+                            }
+                        };
+                        match warp.multipass.create_identity(Some(&username), Some(&phrase)).await {
+                            Ok(_id) =>  match finish_account_creation(&mut warp, notify.clone()).await {
+                                Ok(ident) => {
+                                    let _ = rsp.send(Ok((ident, phrase)));
+                                    break Some(warp);
+                                }
                                 Err(e) => {
-                                    warp.tesseract.lock();
                                     let _ = rsp.send(Err(e));
                                     continue;
                                 }
@@ -186,19 +214,78 @@ async fn handle_login(notify: Arc<Notify>) {
                             }
                         }
                     }
+                    Some(WarpCmd::MultiPass(MultiPassCmd::RecoverIdentity {
+                        mnemonic,
+                        new_passphrase,
+                        rsp,
+                    })) => {
+                        // check the phrase before deleting anything
+                        if let Err(e) = did_from_mnemonic(&mnemonic, None) {
+                            log::info!("invalid recovery phrase: {:?}", e);
+                            let _ = rsp.send(Err(e));
+                            continue;
+                        }
+
+                        // the keypair is rebuilt into a fresh tesseract and warp directory
+                        let tesseract = init_tesseract(true)
+                            .await
+                            .expect("failed to initialize tesseract");
+                        warp = match warp_initialization(tesseract).await {
+                            Ok(w) => w,
+                            Err(e) => {
+                                log::error!("warp init failed: {}", e);
+                                return false;
+                            }
+                        };
+
+                        if let Err(e) = warp.tesseract.unlock(new_passphrase.as_bytes()) {
+                            log::info!("unlock failed: {:?}", e);
+                            let _ = rsp.send(Err(e));
+                            continue;
+                        };
+                        // the username is not part of the keypair. MultiPass picks one until the user changes it
+                        match warp.multipass.create_identity(None, Some(&mnemonic)).await {
+                            Ok(_id) => match finish_account_creation(&mut warp, notify.clone()).await {
+                                Ok(ident) => {
+                                    let _ = rsp.send(Ok(ident));
+                                    break Some(warp);
+                                }
+                                Err(e) => {
+                                    let _ = rsp.send(Err(e));
+                                    continue;
+                                }
+                            },
+                            Err(e) => {
+                                log::error!("failed to recover identity: {}", e);
+                                warp.tesseract.lock();
+                                let _ = rsp.send(Err(e));
+                            }
+                        }
+                    }
                     Some(WarpCmd::MultiPass(MultiPassCmd::TryLogIn { passphrase, rsp })) => {
+                        let policy = login_throttle::LoginPolicy::load();
+                        if let Err(e) = login_attempts.check(&policy, Utc::now()) {
+                            log::info!("login throttled: {}", e);
+                            let _ = rsp.send(Err(e));
+                            continue;
+                        }
                         if let Err(e) = warp.tesseract.unlock(passphrase.as_bytes()) {
                             log::info!("unlock failed: {:?}", e);
+                            login_attempts.record_failure(&policy, Utc::now());
+                            login_attempts.save();
                             let _ = rsp.send(Err(e));
                             continue;
                         };
                         match wait_for_multipass(&mut warp, notify.clone()).await {
                             Ok(ident) => {
+                                login_attempts.reset();
                                 let _ = rsp.send(Ok(ident));
                                 break Some(warp);
                             },
                             Err(e) => {
                                 warp.tesseract.lock();
+                                login_attempts.record_failure(&policy, Utc::now());
+                                login_attempts.save();
                                 let _ = rsp.send(Err(e));
                                 continue;
                             }
@@ -219,12 +306,29 @@ async fn handle_login(notify: Arc<Notify>) {
     drop(warp_cmd_rx);
 
     if let Some(warp) = warp {
-        manager::run(warp, notify).await;
+        manager::run(warp, notify).await
     } else {
         log::info!("warp_runner terminated during initialization");
+        false
     }
 }
 
+// called after create_identity. saves the tesseract once MultiPass is ready
+async fn finish_account_creation(
+    warp: &mut manager::Warp,
+    notify: Arc<Notify>,
+) -> Result<multipass::identity::Identity, Error> {
+    let ident = match wait_for_multipass(warp, notify).await {
+        Ok(ident) => ident,
+        Err(e) => {
+            warp.tesseract.lock();
+            return Err(e);
+        }
+    };
+    save_tesseract(&warp.tesseract)?;
+    Ok(ident)
+}
+
 async fn wait_for_multipass(
     warp: &mut manager::Warp,
     notify: Arc<Notify>,
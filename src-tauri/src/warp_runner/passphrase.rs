@@ -0,0 +1,155 @@
+//! checks passphrases when an account is created. the feedback is a list of keys from the locales folder,
+//! e.g. "passphrase.too-short", so the UI can show it in the user's language.
+//!
+//! the entropy estimate is deliberately simple: the size of the character pool the passphrase draws from,
+//! counted only for characters which don't repeat or continue a sequence.
+
+use serde::{Deserialize, Serialize};
+
+use crate::state::configuration::read_login_config;
+
+// the policy can be changed by editing this key in login_config.json
+const POLICY_KEY: &str = "passphrase_policy";
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PassphrasePolicy {
+    // in characters, not bytes
+    #[serde(default = "min_length_default")]
+    pub min_length: usize,
+    #[serde(default = "min_entropy_bits_default")]
+    pub min_entropy_bits: f64,
+}
+
+fn min_length_default() -> usize {
+    8
+}
+
+fn min_entropy_bits_default() -> f64 {
+    40.0
+}
+
+impl Default for PassphrasePolicy {
+    fn default() -> Self {
+        Self {
+            min_length: min_length_default(),
+            min_entropy_bits: min_entropy_bits_default(),
+        }
+    }
+}
+
+impl PassphrasePolicy {
+    pub fn load() -> Self {
+        read_login_config(POLICY_KEY).unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PassphraseStrength {
+    pub entropy_bits: f64,
+    pub acceptable: bool,
+    // locale keys. empty if there is nothing to improve
+    pub feedback: Vec<String>,
+}
+
+pub fn check(passphrase: &str, policy: &PassphrasePolicy) -> PassphraseStrength {
+    let chars: Vec<char> = passphrase.chars().collect();
+    let entropy_bits = estimate_entropy(&chars);
+    let mut feedback = vec![];
+
+    let too_short = chars.len() < policy.min_length;
+    if too_short {
+        feedback.push("passphrase.too-short");
+    }
+    if character_classes(&chars) < 3 {
+        feedback.push("passphrase.add-variety");
+    }
+    if effective_length(&chars) * 2 < chars.len() {
+        feedback.push("passphrase.avoid-repeats");
+    }
+    let too_weak = entropy_bits < policy.min_entropy_bits;
+    if too_weak {
+        feedback.push("passphrase.too-weak");
+    }
+
+    PassphraseStrength {
+        entropy_bits,
+        acceptable: !too_short && !too_weak,
+        feedback: feedback.into_iter().map(String::from).collect(),
+    }
+}
+
+fn estimate_entropy(chars: &[char]) -> f64 {
+    let pool: f64 = [
+        (chars.iter().any(|c| c.is_ascii_lowercase()), 26.0),
+        (chars.iter().any(|c| c.is_ascii_uppercase()), 26.0),
+        (chars.iter().any(|c| c.is_ascii_digit()), 10.0),
+        (
+            chars.iter().any(|c| c.is_ascii_punctuation() || *c == ' '),
+            33.0,
+        ),
+        (chars.iter().any(|c| !c.is_ascii()), 100.0),
+    ]
+    .iter()
+    .filter(|(present, _)| *present)
+    .map(|(_, size)| size)
+    .sum();
+
+    if pool == 0.0 {
+        return 0.0;
+    }
+    effective_length(chars) as f64 * pool.log2()
+}
+
+fn character_classes(chars: &[char]) -> usize {
+    [
+        chars.iter().any(|c| c.is_ascii_lowercase()),
+        chars.iter().any(|c| c.is_ascii_uppercase()),
+        chars.iter().any(|c| c.is_ascii_digit()),
+        chars.iter().any(|c| !c.is_ascii_alphanumeric()),
+    ]
+    .iter()
+    .filter(|present| **present)
+    .count()
+}
+
+// characters which repeat the previous one ("aaa") or continue a sequence ("abc", "321") add little
+fn effective_length(chars: &[char]) -> usize {
+    let mut len = chars.len().min(1);
+    for window in chars.windows(2) {
+        let diff = window[1] as i64 - window[0] as i64;
+        if diff.abs() > 1 {
+            len += 1;
+        }
+    }
+    len
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn short_passphrase_is_rejected() {
+        let strength = check("aB3$", &PassphrasePolicy::default());
+        assert!(!strength.acceptable);
+        assert!(strength
+            .feedback
+            .contains(&"passphrase.too-short".to_string()));
+    }
+
+    #[test]
+    fn repeats_and_sequences_are_weak() {
+        let strength = check("aaaaaaaaabcdefgh", &PassphrasePolicy::default());
+        assert!(!strength.acceptable);
+        assert!(strength
+            .feedback
+            .contains(&"passphrase.avoid-repeats".to_string()));
+    }
+
+    #[test]
+    fn varied_passphrase_is_accepted() {
+        let strength = check("correct Horse 7 battery!", &PassphrasePolicy::default());
+        assert!(strength.acceptable);
+        assert!(strength.feedback.is_empty());
+    }
+}
@@ -0,0 +1,264 @@
+//! safety numbers let two people check, in person or over another channel, that they are talking to the right key.
+//! both sides compute the same number from the two DIDs. since a did:key encodes the public key, the DIDs are
+//! used as the key material.
+//!
+//! verifying a contact stores the safety number which was compared. the verification only holds while the two keys
+//! still produce that number.
+//!
+//! a DID is the key, so a contact with a new key is a new DID, and nothing ties it to the old one except what the
+//! contact claims about itself. two cases are reported:
+//! - the safety number with a verified DID changed. this happens when the user's own key changed
+//! - someone who sends a friend request or becomes a friend uses the name of a verified contact but a different
+//!   key. this is either that contact with a new key or someone pretending to be them. either way the user has to
+//!   verify them again before trusting them
+//!
+//! verified contacts are stored in State. State keeps a copy here via `set_verified_contacts` so that the
+//! warp_runner can warn about these.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::RwLock,
+};
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use warp::{crypto::DID, multipass::identity::Identity};
+
+// included in the hash and the QR code so that the scheme can be changed later
+const VERSION: u8 = 1;
+// makes it expensive to search for a key whose safety number resembles someone else's. the result for each DID is
+// kept in the Registry, since verified contacts are compared on every friend event and when they are shown
+const ITERATIONS: usize = 5200;
+// each party contributes 30 digits
+const DIGIT_GROUPS_PER_KEY: usize = 6;
+
+static VERIFIED: Lazy<RwLock<Registry>> = Lazy::new(|| RwLock::new(Registry::default()));
+
+#[derive(Default)]
+struct Registry {
+    own: Option<DID>,
+    verified: HashMap<DID, VerifiedKey>,
+    // new DIDs which were already reported. avoids warning on every event from the same identity
+    warned: HashSet<DID>,
+    // `fingerprint_bytes` of `own` and of the verified DIDs
+    fingerprints: HashMap<DID, Vec<u8>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifiedKey {
+    pub username: String,
+    // `SafetyNumber::qr_code` at the time of verification. it covers both keys
+    #[serde(default)]
+    pub safety_number: String,
+    pub verified_at: DateTime<Utc>,
+}
+
+impl VerifiedKey {
+    pub fn new(own: &DID, did: &DID, username: String) -> Self {
+        Self {
+            username,
+            safety_number: safety_number(own, did).qr_code,
+            verified_at: Utc::now(),
+        }
+    }
+
+    /// false if either key changed since the contact was verified
+    pub fn matches(&self, own: &DID, did: &DID) -> bool {
+        self.safety_number == safety_number(own, did).qr_code
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SafetyNumber {
+    // 60 digits in groups of 5, separated by spaces
+    pub digits: String,
+    // compared by scanning the other person's QR code
+    pub qr_code: String,
+}
+
+pub fn safety_number(own: &DID, other: &DID) -> SafetyNumber {
+    let own = cached_fingerprint(own);
+    let other = cached_fingerprint(other);
+    // both parties must compute the same number, so the order can't depend on who is asking
+    let (first, second) = if own <= other {
+        (own, other)
+    } else {
+        (other, own)
+    };
+
+    let groups: Vec<String> = first
+        .chunks(5)
+        .chain(second.chunks(5))
+        .take(DIGIT_GROUPS_PER_KEY * 2)
+        .map(|chunk| {
+            let n = chunk.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+            format!("{:05}", n % 100_000)
+        })
+        .collect();
+
+    SafetyNumber {
+        digits: groups.join(" "),
+        qr_code: format!("{VERSION}:{}:{}", to_hex(&first), to_hex(&second)),
+    }
+}
+
+/// `own`: the DID of the account
+pub fn set_verified_contacts(own: DID, verified: HashMap<DID, VerifiedKey>) {
+    if let Ok(mut registry) = VERIFIED.write() {
+        registry.own = Some(own);
+        registry.verified = verified;
+        let Registry {
+            own,
+            verified,
+            fingerprints,
+            ..
+        } = &mut *registry;
+        fingerprints.retain(|did, _| own.as_ref() == Some(did) || verified.contains_key(did));
+    }
+}
+
+/// returns the verified DID which `identity` may replace, see the module docs. `claims_contact` is true if the
+/// identity sent a friend request or became a friend. only reports each DID once per session.
+pub fn changed_verified_key(identity: &Identity, claims_contact: bool) -> Option<DID> {
+    let did = identity.did_key();
+    // comparing the keys takes the lock again to read the fingerprints, so it is released in between
+    let (own, verified) = {
+        let registry = VERIFIED.read().ok()?;
+        (registry.own.clone()?, registry.verified.clone())
+    };
+
+    let previous = find_changed_key(&verified, &own, &did, &identity.username(), claims_contact)?;
+    if !VERIFIED.write().ok()?.warned.insert(did) {
+        return None;
+    }
+    Some(previous)
+}
+
+fn find_changed_key(
+    verified: &HashMap<DID, VerifiedKey>,
+    own: &DID,
+    did: &DID,
+    username: &str,
+    claims_contact: bool,
+) -> Option<DID> {
+    match verified.get(did) {
+        Some(key) if key.matches(own, did) => None,
+        Some(_) => Some(did.clone()),
+        // other identities can use any name, so only those which want to be a contact are compared
+        None if claims_contact => verified
+            .iter()
+            .find(|(_, key)| key.username == username)
+            .map(|(previous, _)| previous.clone()),
+        None => None,
+    }
+}
+
+// other DIDs aren't kept, so the cache only grows with the verified contacts
+fn cached_fingerprint(did: &DID) -> Vec<u8> {
+    if let Some(bytes) = VERIFIED
+        .read()
+        .ok()
+        .and_then(|registry| registry.fingerprints.get(did).cloned())
+    {
+        return bytes;
+    }
+    // computed without holding the lock
+    let bytes = fingerprint_bytes(did);
+    if let Ok(mut registry) = VERIFIED.write() {
+        if registry.own.as_ref() == Some(did) || registry.verified.contains_key(did) {
+            registry.fingerprints.insert(did.clone(), bytes.clone());
+        }
+    }
+    bytes
+}
+
+fn fingerprint_bytes(did: &DID) -> Vec<u8> {
+    let key = did.to_string();
+    let mut hash = Sha512::new()
+        .chain_update([VERSION])
+        .chain_update(key.as_bytes())
+        .finalize();
+    for _ in 1..ITERATIONS {
+        hash = Sha512::new()
+            .chain_update(hash)
+            .chain_update(key.as_bytes())
+            .finalize();
+    }
+    hash[..DIGIT_GROUPS_PER_KEY * 5].to_vec()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // DID::default() generates a new keypair
+    fn random_did() -> DID {
+        DID::default()
+    }
+
+    #[test]
+    fn both_parties_see_the_same_number() {
+        let alice = random_did();
+        let bob = random_did();
+        assert_eq!(safety_number(&alice, &bob), safety_number(&bob, &alice));
+    }
+
+    #[test]
+    fn number_has_sixty_digits() {
+        let number = safety_number(&random_did(), &random_did());
+        let digits: String = number.digits.split(' ').collect();
+        assert_eq!(digits.len(), 60);
+        assert!(digits.chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn verification_breaks_when_own_key_changes() {
+        let alice = random_did();
+        let bob = random_did();
+        let verified = HashMap::from([(bob.clone(), VerifiedKey::new(&alice, &bob, "bob".into()))]);
+
+        assert_eq!(find_changed_key(&verified, &alice, &bob, "bob", true), None);
+        let new_alice = random_did();
+        assert_eq!(
+            find_changed_key(&verified, &new_alice, &bob, "bob", false),
+            Some(bob)
+        );
+    }
+
+    #[test]
+    fn new_key_claiming_a_verified_contact_is_reported() {
+        let alice = random_did();
+        let bob = random_did();
+        let verified = HashMap::from([(bob.clone(), VerifiedKey::new(&alice, &bob, "bob".into()))]);
+
+        let new_bob = random_did();
+        assert_eq!(
+            find_changed_key(&verified, &alice, &new_bob, "bob", true),
+            Some(bob)
+        );
+        // only identities which want to be a contact are compared by name
+        assert_eq!(
+            find_changed_key(&verified, &alice, &new_bob, "bob", false),
+            None
+        );
+        assert_eq!(
+            find_changed_key(&verified, &alice, &new_bob, "carol", true),
+            None
+        );
+    }
+
+    #[test]
+    fn number_depends_on_both_keys() {
+        let alice = random_did();
+        assert_ne!(
+            safety_number(&alice, &random_did()),
+            safety_number(&alice, &random_did())
+        );
+    }
+}
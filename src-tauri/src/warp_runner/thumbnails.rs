@@ -0,0 +1,282 @@
+//! thumbnails for files in Constellation. images are decoded and scaled down with the `image` crate, so the
+//! thumbnail stays small no matter how large the original is. svg files are vector graphics and are used as they are,
+//! svgz files once they are decompressed. the `image` crate can't decode heic and avif, so those get a placeholder.
+//!
+//! video thumbnails need ffmpeg to extract a frame. if it isn't installed, or fails, a placeholder is used instead.
+//!
+//! the large size is made on upload and stored in Constellation. the other sizes are made from it when they are
+//! asked for. thumbnails are cached on disk, named after the hash of what they were made from and their size.
+//!
+//! decoding images and running ffmpeg takes a while, so these functions are called with `spawn_blocking`.
+
+use std::{
+    fs,
+    io::{Cursor, Read},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use flate2::read::GzDecoder;
+use image::{imageops::FilterType, DynamicImage, ImageBuffer, ImageOutputFormat, Rgba};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+use warp::logging::tracing::log;
+
+use super::content_index::hash_file;
+use crate::STATIC_ARGS;
+
+// extensions in IMAGE_EXTENSIONS which the `image` crate can't decode with the features in use
+const UNDECODABLE_EXTENSIONS: &[&str] = &["heic", "avif"];
+
+const PLACEHOLDER_COLOR: Rgba<u8> = Rgba([0x2b, 0x2d, 0x31, 0xff]);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailSize {
+    // for lists
+    Small,
+    // for previews. this is the one stored in Constellation
+    Large,
+}
+
+impl ThumbnailSize {
+    /// the longest side of the thumbnail
+    pub fn pixels(self) -> u32 {
+        match self {
+            ThumbnailSize::Small => 64,
+            ThumbnailSize::Large => 256,
+        }
+    }
+}
+
+static FFMPEG_AVAILABLE: Lazy<bool> = Lazy::new(|| {
+    Command::new("ffmpeg")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|s| s.success())
+        .unwrap_or(false)
+});
+
+/// returns a data url of the large size. `extension` is the file's extension without the dot
+pub fn image_thumbnail(
+    contents: &[u8],
+    extension: &str,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let extension = extension.to_ascii_lowercase();
+    match extension.as_str() {
+        "svg" => return Ok(data_url("image/svg+xml", contents)),
+        "svgz" => {
+            let mut svg = vec![];
+            GzDecoder::new(contents).read_to_end(&mut svg)?;
+            return Ok(data_url("image/svg+xml", &svg));
+        }
+        _ => {}
+    }
+    if UNDECODABLE_EXTENSIONS.contains(&extension.as_str()) {
+        log::info!("can't decode .{extension} images. using a placeholder thumbnail");
+        let size = ThumbnailSize::Large.pixels();
+        return placeholder(size, size);
+    }
+
+    let cache = ThumbnailCache::new(STATIC_ARGS.thumbnail_cache_path.clone());
+    let hash = hash_bytes(contents);
+    if let Some(png) = cache.get(&hash, ThumbnailSize::Large) {
+        return Ok(data_url("image/png", &png));
+    }
+
+    let png = make_thumbnail(&image::load_from_memory(contents)?, ThumbnailSize::Large)?;
+    cache.insert(&hash, ThumbnailSize::Large, &png);
+    Ok(data_url("image/png", &png))
+}
+
+/// returns a data url of the large size. falls back to a placeholder if ffmpeg is missing or can't read the video
+pub fn video_thumbnail(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let cache = ThumbnailCache::new(STATIC_ARGS.thumbnail_cache_path.clone());
+    let hash = hash_file(path)?;
+    if let Some(png) = cache.get(&hash, ThumbnailSize::Large) {
+        return Ok(data_url("image/png", &png));
+    }
+
+    // the placeholder isn't cached, so the real thumbnail is made once ffmpeg is installed
+    let size = ThumbnailSize::Large.pixels();
+    if !*FFMPEG_AVAILABLE {
+        log::warn!("ffmpeg not found. using a placeholder thumbnail for videos");
+        return placeholder(size, size * 9 / 16);
+    }
+    let frame = match extract_frame(path) {
+        Ok(frame) => frame,
+        Err(e) => {
+            log::warn!("failed to extract a frame from {}: {e}", path.display());
+            return placeholder(size, size * 9 / 16);
+        }
+    };
+    let png = make_thumbnail(&frame, ThumbnailSize::Large)?;
+    cache.insert(&hash, ThumbnailSize::Large, &png);
+    Ok(data_url("image/png", &png))
+}
+
+/// `thumbnail` is a data url made by this module. returns it at the given size. svg thumbnails scale by themselves
+/// and are returned as they are
+pub fn resize_thumbnail(
+    thumbnail: &str,
+    size: ThumbnailSize,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let png = match thumbnail.strip_prefix("data:image/png;base64,") {
+        Some(encoded) if size != ThumbnailSize::Large => base64::decode(encoded)?,
+        _ => return Ok(thumbnail.to_string()),
+    };
+    let cache = ThumbnailCache::new(STATIC_ARGS.thumbnail_cache_path.clone());
+    let hash = hash_bytes(&png);
+    if let Some(png) = cache.get(&hash, size) {
+        return Ok(data_url("image/png", &png));
+    }
+
+    let resized = make_thumbnail(&image::load_from_memory(&png)?, size)?;
+    cache.insert(&hash, size, &resized);
+    Ok(data_url("image/png", &resized))
+}
+
+fn extract_frame(path: &Path) -> Result<DynamicImage, Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let frame_path = temp_dir.path().join("frame.jpg");
+    let status = Command::new("ffmpeg")
+        .args([
+            "-i",
+            &path.to_string_lossy(),
+            "-vf",
+            "select=eq(pict_type\\,I)",
+            "-frames:v",
+            "1",
+            "-q:v",
+            "2",
+            &frame_path.to_string_lossy(),
+        ])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if !status.success() {
+        return Err(format!("ffmpeg exited with {status}").into());
+    }
+    Ok(image::open(frame_path)?)
+}
+
+fn placeholder(width: u32, height: u32) -> Result<String, Box<dyn std::error::Error>> {
+    let img = ImageBuffer::from_pixel(width, height, PLACEHOLDER_COLOR);
+    let png = encode_png(&DynamicImage::ImageRgba8(img))?;
+    Ok(data_url("image/png", &png))
+}
+
+// scales the image down to fit in a square of the size, keeping its aspect ratio. small images aren't scaled up
+fn make_thumbnail(img: &DynamicImage, size: ThumbnailSize) -> Result<Vec<u8>, image::ImageError> {
+    let pixels = size.pixels();
+    if img.width() <= pixels && img.height() <= pixels {
+        return encode_png(img);
+    }
+    encode_png(&img.resize(pixels, pixels, FilterType::Triangle))
+}
+
+fn encode_png(img: &DynamicImage) -> Result<Vec<u8>, image::ImageError> {
+    let mut png = Cursor::new(vec![]);
+    img.write_to(&mut png, ImageOutputFormat::Png)?;
+    Ok(png.into_inner())
+}
+
+fn data_url(mime: &str, contents: &[u8]) -> String {
+    format!("data:{mime};base64,{}", base64::encode(contents))
+}
+
+// hashed the same way as content_index::hash_file, so a file has the same hash whether or not it's in memory
+fn hash_bytes(contents: &[u8]) -> String {
+    Sha256::digest(contents)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// one png per file and size
+struct ThumbnailCache {
+    dir: PathBuf,
+}
+
+impl ThumbnailCache {
+    fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    // the size in pixels is part of the name, so thumbnails made before a size was changed aren't used
+    fn path(&self, hash: &str, size: ThumbnailSize) -> PathBuf {
+        self.dir.join(format!("{hash}-{}.png", size.pixels()))
+    }
+
+    fn get(&self, hash: &str, size: ThumbnailSize) -> Option<Vec<u8>> {
+        fs::read(self.path(hash, size)).ok()
+    }
+
+    fn insert(&self, hash: &str, size: ThumbnailSize, png: &[u8]) {
+        if let Err(e) = fs::create_dir_all(&self.dir) {
+            log::error!("failed to create thumbnail cache: {e}");
+            return;
+        }
+        if let Err(e) = fs::write(self.path(hash, size), png) {
+            log::error!("failed to save thumbnail: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn large_images_are_scaled_down() {
+        let img = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(1000, 500, PLACEHOLDER_COLOR));
+        let png = make_thumbnail(&img, ThumbnailSize::Large).unwrap();
+        let thumbnail = image::load_from_memory(&png).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (256, 128));
+        let png = make_thumbnail(&img, ThumbnailSize::Small).unwrap();
+        let thumbnail = image::load_from_memory(&png).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (64, 32));
+
+        let small = DynamicImage::ImageRgba8(ImageBuffer::from_pixel(20, 10, PLACEHOLDER_COLOR));
+        let png = make_thumbnail(&small, ThumbnailSize::Large).unwrap();
+        let thumbnail = image::load_from_memory(&png).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (20, 10));
+    }
+
+    #[test]
+    fn svgz_is_decompressed() {
+        use flate2::{write::GzEncoder, Compression};
+        use std::io::Write;
+
+        let svg = b"<svg xmlns=\"http://www.w3.org/2000/svg\"></svg>";
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(svg).unwrap();
+        let svgz = encoder.finish().unwrap();
+
+        assert_eq!(
+            image_thumbnail(&svgz, "svgz").unwrap(),
+            data_url("image/svg+xml", svg)
+        );
+    }
+
+    #[test]
+    fn cache_is_keyed_by_hash_and_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ThumbnailCache::new(dir.path().to_path_buf());
+        cache.insert(&hash_bytes(b"contents"), ThumbnailSize::Large, b"png");
+
+        assert_eq!(
+            cache.get(&hash_bytes(b"contents"), ThumbnailSize::Large),
+            Some(b"png".to_vec())
+        );
+        assert_eq!(
+            cache.get(&hash_bytes(b"contents"), ThumbnailSize::Small),
+            None
+        );
+        assert_eq!(cache.get(&hash_bytes(b"other"), ThumbnailSize::Large), None);
+    }
+}
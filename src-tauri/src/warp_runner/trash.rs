@@ -0,0 +1,132 @@
+//! deleted items are moved into a hidden directory in Constellation rather than removed, so they can be restored.
+//! Constellation items have nowhere to keep where they were deleted from, so that is saved here.
+//!
+//! items in the trash are renamed to the id of their entry, so items with the same name don't clash.
+//!
+//! the name of the trash is reserved: folders can't be created, renamed, moved or copied to it. a folder with that
+//! name which isn't the trash, made before the name was reserved, is renamed when the trash is created. the id of
+//! the trash is saved here to tell them apart.
+
+use std::fs;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use warp::logging::tracing::log;
+
+use crate::STATIC_ARGS;
+
+/// in the root directory. hidden from listings, searches and the folders in the storage stats
+pub const TRASH_DIRECTORY: &str = ".trash";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TrashEntry {
+    pub id: Uuid,
+    pub name: String,
+    // the directory it was deleted from, e.g. "/photos"
+    pub original_directory: String,
+    pub deleted_at: DateTime<Utc>,
+    pub size: usize,
+    pub is_directory: bool,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TrashIndex {
+    entries: Vec<TrashEntry>,
+    // the id of the trash directory. None until it is created
+    #[serde(default)]
+    directory_id: Option<Uuid>,
+}
+
+impl TrashIndex {
+    pub fn load() -> Self {
+        fs::read(&STATIC_ARGS.trash_index_path)
+            .ok()
+            .and_then(|b| serde_json::from_slice(&b).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        let contents = match serde_json::to_vec(self) {
+            Ok(c) => c,
+            Err(e) => {
+                log::error!("failed to serialize trash index: {e}");
+                return;
+            }
+        };
+        if let Err(e) = fs::write(&STATIC_ARGS.trash_index_path, contents) {
+            log::error!("failed to save trash index: {e}");
+        }
+    }
+
+    pub fn entries(&self) -> &[TrashEntry] {
+        &self.entries
+    }
+
+    pub fn directory_id(&self) -> Option<Uuid> {
+        self.directory_id
+    }
+
+    pub fn set_directory_id(&mut self, id: Uuid) {
+        self.directory_id = Some(id);
+    }
+
+    /// removes every entry
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn insert(&mut self, entry: TrashEntry) {
+        self.entries.push(entry);
+    }
+
+    pub fn remove(&mut self, id: Uuid) -> Option<TrashEntry> {
+        let idx = self.entries.iter().position(|e| e.id == id)?;
+        Some(self.entries.remove(idx))
+    }
+
+    pub fn retain(&mut self, f: impl FnMut(&TrashEntry) -> bool) {
+        self.entries.retain(f);
+    }
+
+    /// the entries deleted more than `retention_days` ago
+    pub fn expired(&self, now: DateTime<Utc>, retention_days: u64) -> Vec<Uuid> {
+        let retention = Duration::days(retention_days as i64);
+        self.entries
+            .iter()
+            .filter(|e| now - e.deleted_at > retention)
+            .map(|e| e.id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(deleted_at: DateTime<Utc>) -> TrashEntry {
+        TrashEntry {
+            id: Uuid::new_v4(),
+            name: "cat.png".into(),
+            original_directory: "/photos".into(),
+            deleted_at,
+            size: 10,
+            is_directory: false,
+        }
+    }
+
+    #[test]
+    fn only_old_entries_expire() {
+        let now = Utc::now();
+        let old = entry(now - Duration::days(31));
+        let recent = entry(now - Duration::days(29));
+        let mut index = TrashIndex::default();
+        index.insert(old.clone());
+        index.insert(recent.clone());
+
+        assert_eq!(index.expired(now, 30), vec![old.id]);
+        assert!(index.remove(old.id).is_some());
+        assert!(index.expired(now, 30).is_empty());
+        assert_eq!(index.entries().len(), 1);
+    }
+}
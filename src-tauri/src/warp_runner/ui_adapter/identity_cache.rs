@@ -0,0 +1,112 @@
+//! resolving a DID takes several calls to MultiPass. `did_to_identity` is used for every event and for every
+//! participant of every conversation, so resolved identities are kept here for a while.
+//!
+//! entries expire after IDENTITY_TTL and are dropped early when MultiPass reports that the identity changed
+//! (IdentityUpdate, IdentityOnline, IdentityOffline). the cache is emptied when Uplink locks and before the account
+//! is deleted.
+
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+use warp::crypto::DID;
+
+use crate::state;
+
+pub const IDENTITY_TTL: Duration = Duration::from_secs(5 * 60);
+
+static CACHE: Lazy<RwLock<IdentityCache>> =
+    Lazy::new(|| RwLock::new(IdentityCache::new(IDENTITY_TTL)));
+
+pub fn get(did: &DID) -> Option<state::Identity> {
+    CACHE.read().ok()?.get(did, Instant::now())
+}
+
+pub fn insert(identity: state::Identity) {
+    if let Ok(mut cache) = CACHE.write() {
+        cache.insert(identity, Instant::now());
+    }
+}
+
+pub fn invalidate(did: &DID) {
+    if let Ok(mut cache) = CACHE.write() {
+        cache.entries.remove(did);
+    }
+}
+
+pub fn clear() {
+    if let Ok(mut cache) = CACHE.write() {
+        cache.entries.clear();
+    }
+}
+
+struct IdentityCache {
+    ttl: Duration,
+    // the identity and when it was resolved
+    entries: HashMap<DID, (Instant, state::Identity)>,
+}
+
+impl IdentityCache {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn get(&self, did: &DID, now: Instant) -> Option<state::Identity> {
+        match self.entries.get(did) {
+            Some((resolved_at, identity)) if now.duration_since(*resolved_at) < self.ttl => {
+                Some(identity.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, identity: state::Identity, now: Instant) {
+        // expired entries are only removed here, so the cache can't grow forever
+        let ttl = self.ttl;
+        self.entries
+            .retain(|_, (resolved_at, _)| now.duration_since(*resolved_at) < ttl);
+        self.entries.insert(identity.did_key(), (now, identity));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use warp::multipass::identity::Identity;
+
+    fn identity(did: &DID) -> state::Identity {
+        let mut identity = Identity::default();
+        identity.set_did_key(did.clone());
+        state::Identity::from(identity)
+    }
+
+    #[test]
+    fn entries_expire() {
+        let did = DID::default();
+        let start = Instant::now();
+        let mut cache = IdentityCache::new(Duration::from_secs(60));
+        cache.insert(identity(&did), start);
+
+        assert!(cache.get(&did, start + Duration::from_secs(59)).is_some());
+        assert!(cache.get(&did, start + Duration::from_secs(60)).is_none());
+    }
+
+    #[test]
+    fn insert_drops_expired_entries() {
+        let old = DID::default();
+        let new = DID::default();
+        let start = Instant::now();
+        let mut cache = IdentityCache::new(Duration::from_secs(60));
+        cache.insert(identity(&old), start);
+        cache.insert(identity(&new), start + Duration::from_secs(90));
+
+        assert!(!cache.entries.contains_key(&old));
+        assert!(cache.entries.contains_key(&new));
+    }
+}
@@ -7,7 +7,7 @@ use warp::{
 };
 
 use super::Message;
-use crate::warp_runner::ui_adapter::convert_raygun_message;
+use crate::warp_runner::{link_preview::LinkPreview, ui_adapter::convert_raygun_message};
 
 pub enum MessageEvent {
     Received {
@@ -40,6 +40,12 @@ pub enum MessageEvent {
         conversation_id: Uuid,
         participant: DID,
     },
+    // sent some time after Received/Sent/Edited, once the links in the message have been fetched
+    LinkPreviews {
+        conversation_id: Uuid,
+        message_id: Uuid,
+        previews: Vec<LinkPreview>,
+    },
 }
 
 pub async fn convert_message_event(
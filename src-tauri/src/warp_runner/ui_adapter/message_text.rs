@@ -0,0 +1,567 @@
+//! turns the raw lines of a raygun::Message into a small AST which the UI can render without doing any parsing itself.
+//! supports a subset of markdown (bold, italic, inline code, fenced code blocks, links), @mentions and emoji shortcodes.
+//!
+//! the AST is serialized and sent to the frontend along with a pre-rendered, HTML escaped version of the message. lit-html
+//! escapes text by default, but the `html` field is meant to be inserted with `unsafeHTML`, so everything coming from the
+//! peer must go through `escape_html` first.
+
+use serde::{Deserialize, Serialize};
+use warp::crypto::DID;
+
+use crate::state::{self, notifications::NotificationPriority};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParsedMessage {
+    pub blocks: Vec<Block>,
+    pub html: String,
+    pub mentions_me: bool,
+    pub priority: NotificationPriority,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum Block {
+    Paragraph(Vec<Inline>),
+    CodeBlock {
+        language: Option<String>,
+        code: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum Inline {
+    Text(String),
+    Bold(Vec<Inline>),
+    Italic(Vec<Inline>),
+    Code(String),
+    Link { url: String, text: String },
+    Mention { username: String, did: DID },
+    Emoji { shortcode: String, emoji: String },
+}
+
+/// parses the lines of a message. `participants` is used to resolve @mentions and should include the current user.
+pub fn parse_message(
+    lines: &[String],
+    participants: &[state::Identity],
+    own_did: &DID,
+) -> ParsedMessage {
+    let blocks = parse_blocks(lines, participants);
+    let mentions_me = blocks.iter().any(|block| match block {
+        Block::Paragraph(inlines) => mentions(inlines, own_did),
+        Block::CodeBlock { .. } => false,
+    });
+    let html = to_html(&blocks, own_did);
+
+    ParsedMessage {
+        blocks,
+        html,
+        mentions_me,
+        priority: if mentions_me {
+            NotificationPriority::High
+        } else {
+            NotificationPriority::Normal
+        },
+    }
+}
+
+/// returns the links in a message, in order and without duplicates. links inside code are ignored.
+pub fn find_urls(lines: &[String]) -> Vec<String> {
+    fn collect(inlines: &[Inline], urls: &mut Vec<String>) {
+        for inline in inlines {
+            match inline {
+                Inline::Link { url, .. } => {
+                    if !urls.contains(url) {
+                        urls.push(url.clone());
+                    }
+                }
+                Inline::Bold(inner) | Inline::Italic(inner) => collect(inner, urls),
+                _ => {}
+            }
+        }
+    }
+
+    let mut urls = vec![];
+    for block in parse_blocks(lines, &[]) {
+        if let Block::Paragraph(inlines) = block {
+            collect(&inlines, &mut urls);
+        }
+    }
+    urls
+}
+
+fn parse_blocks(lines: &[String], participants: &[state::Identity]) -> Vec<Block> {
+    let mut blocks = vec![];
+    let mut code_block: Option<(Option<String>, Vec<&str>)> = None;
+
+    for line in lines.iter().flat_map(|x| x.lines()) {
+        if let Some(rest) = line.trim_start().strip_prefix("```") {
+            match code_block.take() {
+                Some((language, code)) => {
+                    blocks.push(Block::CodeBlock {
+                        language,
+                        code: code.join("\n"),
+                    });
+                }
+                None => {
+                    let language = rest.trim();
+                    let language = if language.is_empty() {
+                        None
+                    } else {
+                        Some(language.to_string())
+                    };
+                    code_block = Some((language, vec![]));
+                }
+            }
+            continue;
+        }
+
+        match code_block.as_mut() {
+            Some((_, code)) => code.push(line),
+            None => {
+                let chars: Vec<char> = line.chars().collect();
+                blocks.push(Block::Paragraph(parse_inline(&chars, participants)));
+            }
+        }
+    }
+
+    // an unterminated code block is treated as if it was closed at the end of the message
+    if let Some((language, code)) = code_block {
+        blocks.push(Block::CodeBlock {
+            language,
+            code: code.join("\n"),
+        });
+    }
+
+    blocks
+}
+
+pub fn escape_html(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn mentions(inlines: &[Inline], did: &DID) -> bool {
+    inlines.iter().any(|inline| match inline {
+        Inline::Mention { did: mentioned, .. } => mentioned == did,
+        Inline::Bold(inner) | Inline::Italic(inner) => mentions(inner, did),
+        _ => false,
+    })
+}
+
+fn parse_inline(chars: &[char], participants: &[state::Identity]) -> Vec<Inline> {
+    let mut ret = vec![];
+    let mut text = String::new();
+    let mut idx = 0;
+
+    let push_text = |ret: &mut Vec<Inline>, text: &mut String| {
+        if !text.is_empty() {
+            ret.push(Inline::Text(std::mem::take(text)));
+        }
+    };
+
+    while idx < chars.len() {
+        let at_word_start = idx == 0 || chars[idx - 1].is_whitespace();
+
+        let parsed = match chars[idx] {
+            '`' => find(chars, idx + 1, &['`']).map(|end| {
+                let code: String = chars[idx + 1..end].iter().collect();
+                (Inline::Code(code), end + 1)
+            }),
+            '*' if chars.get(idx + 1) == Some(&'*') => find_non_empty(chars, idx + 2, &['*', '*'])
+                .map(|end| {
+                    let inner = parse_inline(&chars[idx + 2..end], participants);
+                    (Inline::Bold(inner), end + 2)
+                }),
+            c @ ('*' | '_') if c == '*' || at_word_start => find_non_empty(chars, idx + 1, &[c])
+                .map(|end| {
+                    let inner = parse_inline(&chars[idx + 1..end], participants);
+                    (Inline::Italic(inner), end + 1)
+                }),
+            '[' => parse_markdown_link(chars, idx),
+            '@' if at_word_start => parse_mention(chars, idx, participants),
+            ':' => parse_emoji(chars, idx),
+            'h' if at_word_start => parse_url(chars, idx),
+            _ => None,
+        };
+
+        match parsed {
+            Some((inline, next)) => {
+                push_text(&mut ret, &mut text);
+                ret.push(inline);
+                idx = next;
+            }
+            None => {
+                text.push(chars[idx]);
+                idx += 1;
+            }
+        }
+    }
+    push_text(&mut ret, &mut text);
+    ret
+}
+
+// returns the index at which `pattern` next occurs, starting at `from`
+fn find(chars: &[char], from: usize, pattern: &[char]) -> Option<usize> {
+    if from > chars.len() {
+        return None;
+    }
+    chars[from..]
+        .windows(pattern.len())
+        .position(|window| window == pattern)
+        .map(|pos| pos + from)
+}
+
+// like find() but the delimited text must not be empty or start with whitespace. prevents "2 * 3 * 4" from becoming italic
+fn find_non_empty(chars: &[char], from: usize, pattern: &[char]) -> Option<usize> {
+    match chars.get(from) {
+        Some(c) if !c.is_whitespace() => {}
+        _ => return None,
+    }
+    find(chars, from, pattern).filter(|end| *end > from)
+}
+
+fn parse_markdown_link(chars: &[char], idx: usize) -> Option<(Inline, usize)> {
+    let text_end = find(chars, idx + 1, &[']'])?;
+    if chars.get(text_end + 1) != Some(&'(') {
+        return None;
+    }
+    let url_end = find(chars, text_end + 2, &[')'])?;
+    let url: String = chars[text_end + 2..url_end].iter().collect();
+    if !is_safe_url(&url) {
+        return None;
+    }
+    let text: String = chars[idx + 1..text_end].iter().collect();
+    Some((Inline::Link { url, text }, url_end + 1))
+}
+
+fn parse_url(chars: &[char], idx: usize) -> Option<(Inline, usize)> {
+    let mut end = chars[idx..]
+        .iter()
+        .position(|c| c.is_whitespace())
+        .map(|pos| pos + idx)
+        .unwrap_or(chars.len());
+    // punctuation at the end of a sentence usually isn't part of the link
+    while end > idx && matches!(chars[end - 1], '.' | ',' | ';' | ':' | '!' | '?' | ')') {
+        end -= 1;
+    }
+    let url: String = chars[idx..end].iter().collect();
+    if !is_safe_url(&url) || url.ends_with("://") {
+        return None;
+    }
+    Some((
+        Inline::Link {
+            text: url.clone(),
+            url,
+        },
+        end,
+    ))
+}
+
+fn is_safe_url(url: &str) -> bool {
+    let lower = url.to_lowercase();
+    lower.starts_with("https://") || lower.starts_with("http://")
+}
+
+fn parse_mention(
+    chars: &[char],
+    idx: usize,
+    participants: &[state::Identity],
+) -> Option<(Inline, usize)> {
+    let end = chars[idx + 1..]
+        .iter()
+        .position(|c| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.')))
+        .map(|pos| pos + idx + 1)
+        .unwrap_or(chars.len());
+    // a username can't end with a '.'. it's probably the end of a sentence
+    let mut end = end;
+    while end > idx + 1 && chars[end - 1] == '.' {
+        end -= 1;
+    }
+    let username: String = chars[idx + 1..end].iter().collect();
+    if username.is_empty() {
+        return None;
+    }
+    let identity = participants
+        .iter()
+        .find(|x| x.username().eq_ignore_ascii_case(&username))?;
+    Some((
+        Inline::Mention {
+            username: identity.username(),
+            did: identity.did_key(),
+        },
+        end,
+    ))
+}
+
+fn parse_emoji(chars: &[char], idx: usize) -> Option<(Inline, usize)> {
+    let end = find(chars, idx + 1, &[':'])?;
+    let shortcode: String = chars[idx + 1..end].iter().collect();
+    let emoji = emoji_for_shortcode(&shortcode)?;
+    Some((
+        Inline::Emoji {
+            shortcode,
+            emoji: emoji.to_string(),
+        },
+        end + 1,
+    ))
+}
+
+fn emoji_for_shortcode(shortcode: &str) -> Option<&'static str> {
+    let emoji = match shortcode {
+        "smile" => "😄",
+        "grin" => "😁",
+        "joy" => "😂",
+        "laughing" => "😆",
+        "wink" => "😉",
+        "slightly_smiling_face" => "🙂",
+        "heart_eyes" => "😍",
+        "sunglasses" => "😎",
+        "thinking" => "🤔",
+        "open_mouth" => "😮",
+        "cry" => "😢",
+        "sob" => "😭",
+        "rage" => "😡",
+        "heart" => "❤️",
+        "thumbsup" | "+1" => "👍",
+        "thumbsdown" | "-1" => "👎",
+        "ok_hand" => "👌",
+        "clap" => "👏",
+        "pray" => "🙏",
+        "wave" => "👋",
+        "eyes" => "👀",
+        "fire" => "🔥",
+        "tada" => "🎉",
+        "rocket" => "🚀",
+        "100" => "💯",
+        "white_check_mark" => "✅",
+        "x" => "❌",
+        "warning" => "⚠️",
+        _ => return None,
+    };
+    Some(emoji)
+}
+
+fn to_html(blocks: &[Block], own_did: &DID) -> String {
+    let mut html = String::new();
+    for block in blocks {
+        match block {
+            Block::Paragraph(inlines) => {
+                html.push_str("<p>");
+                inlines_to_html(inlines, own_did, &mut html);
+                html.push_str("</p>");
+            }
+            Block::CodeBlock { language, code } => {
+                match language {
+                    Some(language) => {
+                        let language: String = language
+                            .chars()
+                            .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+'))
+                            .collect();
+                        html.push_str(&format!("<pre><code class=\"language-{language}\">"));
+                    }
+                    None => html.push_str("<pre><code>"),
+                }
+                html.push_str(&escape_html(code));
+                html.push_str("</code></pre>");
+            }
+        }
+    }
+    html
+}
+
+fn inlines_to_html(inlines: &[Inline], own_did: &DID, html: &mut String) {
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) => html.push_str(&escape_html(text)),
+            Inline::Bold(inner) => {
+                html.push_str("<strong>");
+                inlines_to_html(inner, own_did, html);
+                html.push_str("</strong>");
+            }
+            Inline::Italic(inner) => {
+                html.push_str("<em>");
+                inlines_to_html(inner, own_did, html);
+                html.push_str("</em>");
+            }
+            Inline::Code(code) => {
+                html.push_str("<code>");
+                html.push_str(&escape_html(code));
+                html.push_str("</code>");
+            }
+            Inline::Link { url, text } => html.push_str(&format!(
+                "<a href=\"{}\" target=\"_blank\" rel=\"noopener noreferrer\">{}</a>",
+                escape_html(url),
+                escape_html(text)
+            )),
+            Inline::Mention { username, did } => {
+                let class = if did == own_did {
+                    "mention mention-self"
+                } else {
+                    "mention"
+                };
+                html.push_str(&format!(
+                    "<span class=\"{class}\" data-did=\"{}\">@{}</span>",
+                    escape_html(&did.to_string()),
+                    escape_html(username)
+                ));
+            }
+            Inline::Emoji { shortcode, emoji } => html.push_str(&format!(
+                "<span class=\"emoji\" title=\"{}\">{emoji}</span>",
+                escape_html(shortcode)
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn paragraph(input: &str) -> Vec<Inline> {
+        let parsed = parse_message(&[input.to_string()], &[], &DID::default());
+        match parsed.blocks.first() {
+            Some(Block::Paragraph(inlines)) => inlines.clone(),
+            other => panic!("expected a paragraph, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_bold_italic_and_code() {
+        let r = paragraph("**bold** and *it* `x < y`");
+        assert_eq!(
+            r,
+            vec![
+                Inline::Bold(vec![Inline::Text("bold".into())]),
+                Inline::Text(" and ".into()),
+                Inline::Italic(vec![Inline::Text("it".into())]),
+                Inline::Text(" ".into()),
+                Inline::Code("x < y".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_arithmetic_is_not_italic() {
+        let r = paragraph("2 * 3 * 4");
+        assert_eq!(r, vec![Inline::Text("2 * 3 * 4".into())]);
+    }
+
+    #[test]
+    fn parse_links() {
+        let r = paragraph("see https://example.com/a?b=1. or [docs](https://docs.rs)");
+        assert_eq!(
+            r,
+            vec![
+                Inline::Text("see ".into()),
+                Inline::Link {
+                    url: "https://example.com/a?b=1".into(),
+                    text: "https://example.com/a?b=1".into()
+                },
+                Inline::Text(". or ".into()),
+                Inline::Link {
+                    url: "https://docs.rs".into(),
+                    text: "docs".into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_javascript_links() {
+        let r = paragraph("[click](javascript:alert(1))");
+        assert!(r.iter().all(|x| matches!(x, Inline::Text(_))));
+    }
+
+    #[test]
+    fn parse_code_block_with_language() {
+        let lines = vec!["```rust".into(), "fn main() {}".into(), "```".into()];
+        let parsed = parse_message(&lines, &[], &DID::default());
+        assert_eq!(
+            parsed.blocks,
+            vec![Block::CodeBlock {
+                language: Some("rust".into()),
+                code: "fn main() {}".into()
+            }]
+        );
+        assert_eq!(
+            parsed.html,
+            "<pre><code class=\"language-rust\">fn main() {}</code></pre>"
+        );
+    }
+
+    #[test]
+    fn parse_emoji_shortcodes() {
+        let r = paragraph("nice :tada: :not_an_emoji:");
+        assert_eq!(
+            r,
+            vec![
+                Inline::Text("nice ".into()),
+                Inline::Emoji {
+                    shortcode: "tada".into(),
+                    emoji: "🎉".into()
+                },
+                Inline::Text(" :not_an_emoji:".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn escape_html_in_text() {
+        let parsed = parse_message(
+            &["<script>alert('hi')</script>".into()],
+            &[],
+            &DID::default(),
+        );
+        assert_eq!(
+            parsed.html,
+            "<p>&lt;script&gt;alert(&#x27;hi&#x27;)&lt;/script&gt;</p>"
+        );
+    }
+
+    #[test]
+    fn unresolved_mention_is_text() {
+        let parsed = parse_message(&["hi @nobody".into()], &[], &DID::default());
+        assert!(!parsed.mentions_me);
+        assert_eq!(parsed.priority, NotificationPriority::Normal);
+    }
+
+    #[test]
+    fn mentioning_me_raises_the_priority() {
+        let me = DID::default();
+        let mut identity = warp::multipass::identity::Identity::default();
+        identity.set_did_key(me.clone());
+        identity.set_username("alice");
+        let participants = vec![state::Identity::from(identity)];
+
+        let parsed = parse_message(&["hi @Alice.".into()], &participants, &me);
+        assert_eq!(
+            parsed.blocks,
+            vec![Block::Paragraph(vec![
+                Inline::Text("hi ".into()),
+                Inline::Mention {
+                    username: "alice".into(),
+                    did: me.clone()
+                },
+                Inline::Text(".".into()),
+            ])]
+        );
+        assert!(parsed.mentions_me);
+        assert_eq!(parsed.priority, NotificationPriority::High);
+
+        // the same mention, read by someone else
+        let parsed = parse_message(&["hi @alice".into()], &participants, &DID::default());
+        assert!(!parsed.mentions_me);
+        assert_eq!(parsed.priority, NotificationPriority::Normal);
+    }
+}
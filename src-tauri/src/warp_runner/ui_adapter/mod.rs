@@ -2,7 +2,9 @@
 //! a translation must be performed by WarpRunner.
 //!
 
+pub mod identity_cache;
 mod message_event;
+pub mod message_text;
 mod multipass_event;
 mod raygun_event;
 
@@ -11,11 +13,23 @@ pub use multipass_event::{convert_multipass_event, MultiPassEvent};
 pub use raygun_event::{convert_raygun_event, RayGunEvent};
 use uuid::Uuid;
 
-use crate::state::{self, chats};
-use futures::{stream::FuturesOrdered, FutureExt, StreamExt};
+use crate::{
+    state::{self, chats},
+    warp_runner::{
+        archive::ConversationArchive,
+        link_preview::{self, LinkPreview, PreviewCache},
+    },
+};
+use chrono::Utc;
+use futures::{future, stream::FuturesOrdered, FutureExt, StreamExt};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::PathBuf,
+    str::FromStr,
+};
 use warp::{
+    constellation::file::File,
     crypto::DID,
     error::Error,
     logging::tracing::log,
@@ -33,6 +47,11 @@ pub struct Message {
     /// needs to be re-rendered. Before the addition of this field, the compose view was
     /// using the message Uuid, but this doesn't change when a message is edited.
     pub key: String,
+    /// filled in by State, which knows the participants needed to resolve @mentions
+    #[serde(default)]
+    pub text: message_text::ParsedMessage,
+    #[serde(default)]
+    pub link_previews: Vec<LinkPreview>,
 }
 
 pub struct ChatAdapter {
@@ -54,16 +73,23 @@ pub async fn convert_raygun_message(
         inner: msg.clone(),
         in_reply_to: reply.and_then(|msg| msg.value().first().cloned()),
         key: Uuid::new_v4().to_string(),
+        text: Default::default(),
+        // links which haven't been fetched yet are handled by link_preview::spawn_fetch
+        link_previews: link_preview::cached_previews(&msg.value(), &PreviewCache::default()).await,
     }
 }
 
 // this function is used in response to warp events. assuming that the DID from these events is valid.
 // Warp sends the Identity over. if the Identity has not been received yet, get_identity will fail for
 // a valid DID.
+// resolved identities are cached. see identity_cache
 pub async fn did_to_identity(
     did: &DID,
     account: &super::Account,
 ) -> Result<state::Identity, Error> {
+    if let Some(identity) = identity_cache::get(did) {
+        return Ok(identity);
+    }
     let identity = match account.get_identity(did.clone().into()).await {
         Ok(list) => list.first().cloned(),
         Err(e) => {
@@ -81,8 +107,11 @@ pub async fn did_to_identity(
                 .identity_platform(&id.did_key())
                 .await
                 .unwrap_or(Platform::Unknown);
-            state::Identity::new(id, status, platform)
+            let identity = state::Identity::new(id, status, platform);
+            identity_cache::insert(identity.clone());
+            identity
         }
+        // not cached. the Identity may be received later
         None => {
             let mut default: Identity = Default::default();
             default.set_did_key(did.clone());
@@ -106,19 +135,13 @@ pub async fn dids_to_identity(
     dids: &[DID],
     account: &super::Account,
 ) -> Result<Vec<state::Identity>, Error> {
-    let mut ret = Vec::new();
-    ret.reserve(dids.len());
-    for id in dids {
-        let ident = did_to_identity(id, account).await?;
-        ret.push(ident);
-    }
-    Ok(ret)
+    future::try_join_all(dids.iter().map(|id| did_to_identity(id, account))).await
 }
 
 pub async fn conversation_to_chat(
     conv: &Conversation,
     account: &super::Account,
-    messaging: &mut super::Messaging,
+    messaging: &super::Messaging,
 ) -> Result<ChatAdapter, Error> {
     // todo: should Chat::participants include self?
     let identities = dids_to_identity(&conv.recipients(), account).await?;
@@ -152,3 +175,72 @@ pub async fn conversation_to_chat(
 
     Ok(adapter)
 }
+
+/// builds a read-only chat from an imported archive. reactions aren't restored.
+/// `directory` is where the archive's attachments were extracted
+pub fn archive_to_chat(archive: &ConversationArchive, directory: PathBuf) -> chats::ArchivedChat {
+    let identities: Vec<state::Identity> = archive
+        .participants
+        .iter()
+        .map(|participant| {
+            let mut identity: Identity = Default::default();
+            identity.set_did_key(DID::from_str(&participant.did).unwrap_or_default());
+            identity.set_username(&participant.username);
+            state::Identity::from(identity)
+        })
+        .collect();
+
+    let first_line = |id: uuid::Uuid| {
+        archive
+            .messages
+            .iter()
+            .find(|msg| msg.id == id)
+            .and_then(|msg| msg.lines.first().cloned())
+    };
+
+    let messages: VecDeque<Message> = archive
+        .messages
+        .iter()
+        .map(|msg| {
+            let mut inner = raygun::Message::default();
+            inner.set_id(msg.id);
+            inner.set_conversation_id(archive.conversation_id);
+            inner.set_sender(DID::from_str(&msg.sender).unwrap_or_default());
+            inner.set_date(msg.date);
+            inner.set_replied(msg.in_reply_to);
+            inner.set_value(msg.lines.clone());
+            inner.set_attachment(
+                msg.attachments
+                    .iter()
+                    .map(|attachment| {
+                        let file = File::new(&attachment.name);
+                        file.set_size(attachment.size);
+                        file
+                    })
+                    .collect(),
+            );
+            Message {
+                inner,
+                in_reply_to: msg.in_reply_to.and_then(first_line),
+                key: Uuid::new_v4().to_string(),
+                text: Default::default(),
+                link_previews: vec![],
+            }
+        })
+        .collect();
+
+    chats::ArchivedChat {
+        chat: chats::Chat {
+            id: archive.conversation_id,
+            participants: identities.iter().map(|x| x.did_key()).collect(),
+            messages,
+            unreads: 0,
+            replying_to: None,
+            typing_indicator: HashMap::new(),
+        },
+        identities,
+        exported_at: archive.exported_at,
+        imported_at: Utc::now(),
+        directory,
+    }
+}
@@ -1,8 +1,8 @@
-use warp::{error::Error, multipass::MultiPassEventKind};
+use warp::{crypto::DID, error::Error, multipass::MultiPassEventKind};
 
 use crate::state::{self};
 
-use super::did_to_identity;
+use super::{did_to_identity, identity_cache};
 
 pub enum MultiPassEvent {
     None,
@@ -16,6 +16,30 @@ pub enum MultiPassEvent {
     Blocked(state::Identity),
     Unblocked(state::Identity),
     IdentityUpdate(state::Identity),
+    // the verification of `previous` no longer holds for `identity`. see warp_runner::safety_number
+    VerifiedKeyChanged {
+        previous: DID,
+        identity: state::Identity,
+    },
+}
+
+impl MultiPassEvent {
+    pub fn identity(&self) -> Option<&state::Identity> {
+        match self {
+            MultiPassEvent::None => None,
+            MultiPassEvent::FriendRequestReceived(identity)
+            | MultiPassEvent::FriendRequestSent(identity)
+            | MultiPassEvent::FriendAdded(identity)
+            | MultiPassEvent::FriendRemoved(identity)
+            | MultiPassEvent::FriendRequestCancelled(identity)
+            | MultiPassEvent::FriendOnline(identity)
+            | MultiPassEvent::FriendOffline(identity)
+            | MultiPassEvent::Blocked(identity)
+            | MultiPassEvent::Unblocked(identity)
+            | MultiPassEvent::IdentityUpdate(identity)
+            | MultiPassEvent::VerifiedKeyChanged { identity, .. } => Some(identity),
+        }
+    }
 }
 
 pub async fn convert_multipass_event(
@@ -48,10 +72,12 @@ pub async fn convert_multipass_event(
             MultiPassEvent::FriendRemoved(identity)
         }
         MultiPassEventKind::IdentityOnline { did } => {
+            identity_cache::invalidate(&did);
             let identity = did_to_identity(&did, account).await?;
             MultiPassEvent::FriendOnline(identity)
         }
         MultiPassEventKind::IdentityOffline { did } => {
+            identity_cache::invalidate(&did);
             let identity = did_to_identity(&did, account).await?;
             MultiPassEvent::FriendOffline(identity)
         }
@@ -64,6 +90,7 @@ pub async fn convert_multipass_event(
             MultiPassEvent::Unblocked(identity)
         }
         MultiPassEventKind::IdentityUpdate { did, .. } => {
+            identity_cache::invalidate(&did);
             let identity = did_to_identity(&did, account).await?;
             MultiPassEvent::IdentityUpdate(identity)
         }
@@ -20,6 +20,21 @@ listen("warp-event", function(state: TauriEvent<any>) {
   load_from_state(state.payload, view);
 });
 
+// auto-lock only counts the window being focused unless input is reported too. reported at most every few seconds
+const ACTIVITY_REPORT_INTERVAL_MS = 5000;
+let last_activity_report = 0;
+function report_activity() {
+  let now = Date.now();
+  if (now - last_activity_report < ACTIVITY_REPORT_INTERVAL_MS) {
+    return;
+  }
+  last_activity_report = now;
+  invoke("report_activity_command");
+}
+for (let event of ["keydown", "mousedown", "mousemove", "wheel", "touchstart"]) {
+  window.addEventListener(event, report_activity, { passive: true });
+}
+
 
 
 let promise_start_sam = invoke("start_sam_command");
@@ -96,7 +111,15 @@ function create_identity(username, password) {
 }
 
 function delete_identity() {
-  let promise_start_sam = invoke("delete_identity_command", {});
+  const password = window.prompt("Enter your password to delete this identity");
+  if (password == null) {
+    return;
+  }
+  const keep_backup = window.confirm("Keep an encrypted backup in your documents folder?");
+  let promise_start_sam = invoke("delete_identity_command", {
+    password: password,
+    keepBackup: keep_backup,
+  });
   promise_start_sam.then(function(result) {
     load_from_state(result, view);
   });
@@ -180,7 +203,7 @@ function load_from_state(state, view) {
         <br>
         <br>
         <md-filled-button
-              label="Delete identity and terminate"
+              label="Delete identity"
               @click=${() => delete_identity()}>
         />
       </div>